@@ -0,0 +1,159 @@
+use crate::clients;
+use crate::config::Mode;
+use crate::json::Json;
+use crate::screenshot::Format;
+use chrono::{DateTime, Local};
+use std::fs;
+use std::path::Path;
+use xlib::{Display, Rect, Window};
+
+/// The window a capture was made of.
+#[derive(Clone, Debug)]
+pub struct WindowInfo {
+    pub id: u64,
+    pub title: Option<String>,
+    pub class: Option<String>,
+}
+
+impl WindowInfo {
+    pub fn new(display: &Display, window: &Window) -> Self {
+        Self {
+            id: window.as_raw(),
+            title: clients::title(display, window),
+            class: clients::class(display, window),
+        }
+    }
+}
+
+/// Describes what was captured, and when.
+#[derive(Clone, Debug)]
+pub struct Capture {
+    pub mode: Mode,
+    /// The captured area, relative to the root window.
+    pub rect: Rect,
+    pub window: Option<WindowInfo>,
+    /// Name of the monitor that shows most of the captured area.
+    pub monitor: Option<String>,
+    pub time: DateTime<Local>,
+}
+
+impl Capture {
+    pub fn new(display: &Display, mode: Mode, rect: Rect) -> Self {
+        Self {
+            mode,
+            rect,
+            window: None,
+            monitor: Self::monitor(display, &rect),
+            time: Local::now(),
+        }
+    }
+
+    /// Describes a capture of the given window.
+    pub fn window(display: &Display, window: &Window) -> Self {
+        Self {
+            window: Some(WindowInfo::new(display, window)),
+            ..Self::new(display, Mode::Window, clients::screen_rect(display, window))
+        }
+    }
+
+    /// Returns the name of the monitor that overlaps the most with the
+    /// given rect.
+    fn monitor(display: &Display, rect: &Rect) -> Option<String> {
+        let overlap = |other: &Rect| {
+            let x = (rect.x + rect.width as i32).min(other.x + other.width as i32)
+                - rect.x.max(other.x);
+            let y = (rect.y + rect.height as i32).min(other.y + other.height as i32)
+                - rect.y.max(other.y);
+            i64::from(x.max(0)) * i64::from(y.max(0))
+        };
+
+        display
+            .get_monitors()
+            .into_iter()
+            .map(|monitor| (overlap(&monitor.rect), monitor.name))
+            .filter(|(area, _)| *area > 0)
+            .max_by_key(|(area, _)| *area)
+            .map(|(_, name)| name)
+    }
+
+    /// Describes the capture as keyword-value pairs, using the registered
+    /// PNG keywords where there is one.
+    pub fn metadata(&self) -> Vec<(&'static str, String)> {
+        let rect = &self.rect;
+        let mut entries = vec![
+            ("Creation Time", self.time.to_rfc2822()),
+            ("Software", format!("xscreen {}", env!("CARGO_PKG_VERSION"))),
+            ("Capture Mode", self.mode.name().to_string()),
+            (
+                "Capture Rect",
+                format!("{}x{}+{}+{}", rect.width, rect.height, rect.x, rect.y),
+            ),
+        ];
+
+        if let Some(window) = &self.window {
+            if let Some(title) = &window.title {
+                entries.push(("Title", title.clone()));
+            }
+            if let Some(class) = &window.class {
+                entries.push(("Window Class", class.clone()));
+            }
+        }
+        entries
+    }
+
+    pub fn to_json(&self) -> Json {
+        let rect = Json::object()
+            .with("x", self.rect.x)
+            .with("y", self.rect.y)
+            .with("width", self.rect.width)
+            .with("height", self.rect.height);
+        let window = self.window.as_ref().map(|window| {
+            Json::object()
+                .with("id", window.id)
+                .with("title", window.title.clone())
+                .with("class", window.class.clone())
+        });
+
+        Json::object()
+            .with("time", self.time.to_rfc3339())
+            .with("software", format!("xscreen {}", env!("CARGO_PKG_VERSION")))
+            .with("mode", self.mode.name())
+            .with("rect", rect)
+            .with("monitor", self.monitor.clone())
+            .with("window", window)
+    }
+
+    /// Describes the capture along with the screenshot saved at `path`.
+    pub fn describe(&self, path: &Path, format: Format) -> Json {
+        let size = fs::metadata(path).map(|m| m.len()).ok();
+        self.to_json()
+            .with("path", path.to_string_lossy().into_owned())
+            .with("format", format.extension())
+            .with("size", size)
+    }
+}
+
+#[cfg(test)]
+impl Capture {
+    /// A capture of a window that needs no display, for tests.
+    pub fn example() -> Self {
+        use chrono::TimeZone;
+
+        Self {
+            mode: Mode::Window,
+            rect: Rect {
+                x: 10,
+                y: 20,
+                width: 640,
+                height: 480,
+            },
+            window: Some(WindowInfo {
+                id: 0x1e0_0003,
+                title: Some(String::from("Terminal")),
+                class: Some(String::from("Alacritty")),
+            }),
+            monitor: None,
+            time: Local.with_ymd_and_hms(2019, 6, 1, 12, 0, 0).unwrap(),
+        }
+    }
+}
@@ -0,0 +1,402 @@
+use std::os::raw::{c_long, c_ulong};
+use xlib::{Atom, Display, Rect, Window};
+
+// ICCCM window state of minimised clients, stored as the first item of `WM_STATE`.
+const ICONIC_STATE: c_long = 3;
+
+/// How many `WM_TRANSIENT_FOR` links are followed, e.g. from a dialog of a
+/// dialog to its main window. Guards against cycles.
+const MAX_TRANSIENT_DEPTH: usize = 16;
+
+/// Value of `_NET_WM_DESKTOP` for windows shown on all desktops.
+const ALL_DESKTOPS: u32 = 0xFFFF_FFFF;
+
+/// Window types the picker skips, unless they are included explicitly.
+pub const EXCLUDED_TYPES: [&str; 3] = ["desktop", "dock", "notification"];
+
+/// Returns the ICCCM state of the given window, or `None` if the window
+/// manager does not manage it as a client.
+fn wm_state(display: &Display, window: &Window) -> Option<c_long> {
+    get_property::<c_long>(display, window, "WM_STATE").first().copied()
+}
+
+/// Checks whether the given window has an EWMH state such as
+/// `_NET_WM_STATE_HIDDEN`.
+pub fn has_state(display: &Display, window: &Window, state: &str) -> bool {
+    let atom = display.intern_atom(state, false);
+    get_property::<c_ulong>(display, window, "_NET_WM_STATE").contains(&atom.0)
+}
+
+/// Returns the EWMH type of the given window, i.e. the first entry of
+/// `_NET_WM_WINDOW_TYPE` in lowercase and without its prefix, such as
+/// `dock`. Windows without a type are `normal`.
+pub fn window_type(display: &Display, window: &Window) -> String {
+    get_property::<c_ulong>(display, window, "_NET_WM_WINDOW_TYPE")
+        .into_iter()
+        .filter_map(|atom| display.get_atom_name(Atom(atom)))
+        .find_map(|name| name.strip_prefix("_NET_WM_WINDOW_TYPE_").map(str::to_lowercase))
+        .unwrap_or_else(|| String::from("normal"))
+}
+
+/// Returns the virtual desktop the given window is on, or `None` if it is
+/// shown on all desktops or the window manager does not support them.
+pub fn desktop(display: &Display, window: &Window) -> Option<u32> {
+    if has_state(display, window, "_NET_WM_STATE_STICKY") {
+        return None;
+    }
+
+    // Xlib may sign-extend 32-bit values, so only the lower half is used.
+    get_property::<c_ulong>(display, window, "_NET_WM_DESKTOP")
+        .first()
+        .map(|desktop| *desktop as u32)
+        .filter(|desktop| *desktop != ALL_DESKTOPS)
+}
+
+/// Checks whether the given window is on the current virtual desktop.
+fn on_current_desktop(display: &Display, window: &Window) -> bool {
+    let root = display.default_window();
+    let current = get_property::<c_ulong>(display, &root, "_NET_CURRENT_DESKTOP");
+
+    match (desktop(display, window), current.first()) {
+        (Some(desktop), Some(current)) => desktop == *current as u32,
+        _ => true,
+    }
+}
+
+/// Whether a client is shown on screen, or why not.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Visibility {
+    /// Mapped on the current virtual desktop. The window may still be
+    /// covered by other windows.
+    Visible,
+    Minimised,
+    OtherDesktop,
+    Unmapped,
+}
+
+impl Visibility {
+    pub fn name(self) -> &'static str {
+        match self {
+            Visibility::Visible => "visible",
+            Visibility::Minimised => "minimised",
+            Visibility::OtherDesktop => "other-desktop",
+            Visibility::Unmapped => "unmapped",
+        }
+    }
+}
+
+/// Determines whether the given window is visible. Window managers unmap
+/// minimised windows and those on other desktops, so these reasons are
+/// checked first.
+pub fn visibility(display: &Display, window: &Window) -> Visibility {
+    if wm_state(display, window) == Some(ICONIC_STATE)
+        || has_state(display, window, "_NET_WM_STATE_HIDDEN")
+    {
+        Visibility::Minimised
+    } else if !on_current_desktop(display, window) {
+        Visibility::OtherDesktop
+    } else if !window.get_attributes().is_some_and(|a| a.viewable) {
+        Visibility::Unmapped
+    } else {
+        Visibility::Visible
+    }
+}
+
+/// Returns the parent of the given window and its children from top to
+/// bottom, or `None` if the window does not exist.
+fn query_tree(display: &Display, window: &Window) -> Option<(Window, Vec<Window>)> {
+    let mut root = 0;
+    let mut parent = 0;
+    let mut ptr = std::ptr::null_mut();
+    let mut length = 0;
+
+    unsafe {
+        let status = x11::xlib::XQueryTree(
+            display.as_raw(),
+            window.as_raw(),
+            &mut root,
+            &mut parent,
+            &mut ptr,
+            &mut length,
+        );
+
+        if status == 0 {
+            return None;
+        }
+        if ptr.is_null() {
+            return Some((Window::from_raw(display, parent), Vec::new()));
+        }
+
+        let windows = std::slice::from_raw_parts(ptr, length as usize)
+            .iter()
+            .rev()
+            .map(|w| Window::from_raw(display, *w))
+            .collect();
+
+        x11::xlib::XFree(ptr as *mut std::ffi::c_void);
+        Some((Window::from_raw(display, parent), windows))
+    }
+}
+
+/// Returns the children of the given window, from top to bottom.
+pub fn get_children(display: &Display, window: &Window) -> Vec<Window> {
+    query_tree(display, window).map_or_else(Vec::new, |(_, children)| children)
+}
+
+/// Returns the ancestor of the given window that is a child of the root,
+/// i.e. the frame of a client under reparenting window managers, or the
+/// window itself if it is a child of the root.
+pub fn top_level(display: &Display, window: &Window) -> Window {
+    let root = display.default_window();
+    let mut window = *window;
+
+    while let Some((parent, _)) = query_tree(display, &window) {
+        if parent.as_raw() == root.as_raw() || parent.as_raw() == 0 {
+            break;
+        }
+        window = parent;
+    }
+    window
+}
+
+/// Returns the clients of an EWMH compliant window manager from top to
+/// bottom, or `None` if the window manager does not maintain
+/// `_NET_CLIENT_LIST_STACKING`.
+fn client_list(display: &Display) -> Option<Vec<Window>> {
+    let root = display.default_window();
+    let ids = get_property::<c_ulong>(display, &root, "_NET_CLIENT_LIST_STACKING");
+    if ids.is_empty() {
+        return None;
+    }
+
+    Some(ids.iter().rev().map(|id| Window::from_raw(display, *id)).collect())
+}
+
+/// Walks the window tree from top to bottom, collecting the clients, i.e.
+/// windows with a `WM_STATE`, at any depth. Window managers may reparent
+/// clients into several levels of frames, but the children of a client are
+/// never clients themselves.
+fn find_clients(display: &Display, window: &Window, clients: &mut Vec<Window>) {
+    for child in get_children(display, window) {
+        if wm_state(display, &child).is_some() {
+            clients.push(child);
+        } else {
+            find_clients(display, &child, clients);
+        }
+    }
+}
+
+/// Returns all clients from top to bottom, including those that are not
+/// visible. Uses the stacking order of the window manager if it supports
+/// EWMH, and otherwise searches the window tree.
+pub fn get_clients(display: &Display) -> Vec<Window> {
+    client_list(display).unwrap_or_else(|| {
+        let mut clients = Vec::new();
+        find_clients(display, &display.default_window(), &mut clients);
+        clients
+    })
+}
+
+/// Returns the visible clients from top to bottom.
+pub fn get_all_windows(display: &Display) -> Vec<Window> {
+    get_clients(display)
+        .into_iter()
+        .filter(|w| visibility(display, w) == Visibility::Visible)
+        .collect()
+}
+
+/// Returns the windows the picker offers from top to bottom, i.e. the
+/// visible clients without those of the `EXCLUDED_TYPES` that are not in
+/// `include_types`.
+pub fn pickable_windows(display: &Display, include_types: &[String]) -> Vec<Window> {
+    get_all_windows(display)
+        .into_iter()
+        .filter(|w| {
+            let kind = window_type(display, w);
+            !EXCLUDED_TYPES.contains(&kind.as_str()) || include_types.contains(&kind)
+        })
+        .collect()
+}
+
+/// Reads a property of the given window as a list of items of type `T`.
+/// Returns an empty list if the property is not set, or if `T` does not
+/// match the format of the property. Note that Xlib returns the items of
+/// 32-bit properties as `c_long`s, i.e. `u64` on 64-bit systems.
+pub fn get_property<T: Copy>(display: &Display, window: &Window, name: &str) -> Vec<T> {
+    let atom = display.intern_atom(name, false);
+    let mut actual_type = 0;
+    let mut format = 0;
+    let mut length = 0;
+    let mut bytes_after_return = 0;
+    let mut ptr = std::ptr::null_mut();
+
+    unsafe {
+        x11::xlib::XGetWindowProperty(
+            display.as_raw(),
+            window.as_raw(),
+            atom.0,
+            0,        // offset
+            i64::MAX, // 32-bit multiples of data to be read
+            0,        // delete
+            0,        // req type
+            &mut actual_type,
+            &mut format,
+            &mut length,
+            &mut bytes_after_return,
+            &mut ptr,
+        );
+
+        if ptr.is_null() {
+            return Vec::new();
+        }
+
+        let size = match format {
+            8 => 1,
+            16 => std::mem::size_of::<std::os::raw::c_short>(),
+            32 => std::mem::size_of::<std::os::raw::c_long>(),
+            _ => 0,
+        };
+        let items = if size == std::mem::size_of::<T>() {
+            std::slice::from_raw_parts(ptr as *const T, length as usize).to_vec()
+        } else {
+            Vec::new()
+        };
+
+        x11::xlib::XFree(ptr as *mut std::ffi::c_void);
+        items
+    }
+}
+
+/// Reads a text property of the given window.
+fn get_text(display: &Display, window: &Window, name: &str) -> Option<String> {
+    let bytes = get_property::<u8>(display, window, name);
+    if bytes.is_empty() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Returns the ID of the process that owns the given window, as set by
+/// the client in `_NET_WM_PID`.
+pub fn pid(display: &Display, window: &Window) -> Option<u32> {
+    get_property::<c_ulong>(display, window, "_NET_WM_PID")
+        .first()
+        .map(|pid| *pid as u32)
+}
+
+/// Returns the title of the given window, preferring the UTF-8 encoded
+/// `_NET_WM_NAME` over `WM_NAME`.
+pub fn title(display: &Display, window: &Window) -> Option<String> {
+    get_text(display, window, "_NET_WM_NAME").or_else(|| get_text(display, window, "WM_NAME"))
+}
+
+/// Returns the class of the given window, i.e. the second of the two
+/// null-separated strings in `WM_CLASS`.
+pub fn class(display: &Display, window: &Window) -> Option<String> {
+    get_text(display, window, "WM_CLASS")?
+        .split('\0')
+        .nth(1)
+        .filter(|class| !class.is_empty())
+        .map(String::from)
+}
+
+/// Returns the position and size of the given window, relative to the root.
+pub fn screen_rect(display: &Display, window: &Window) -> Rect {
+    let rect = window.get_rect();
+    let (x, y) = display.translate_coordinates(window, 0, 0);
+    Rect {
+        width: rect.width,
+        height: rect.height,
+        x,
+        y,
+    }
+}
+
+/// Checks whether the window is a transient window, such as a dialog, of
+/// the given parent, directly or through other transient windows.
+fn is_transient_of(display: &Display, window: &Window, parent: &Window) -> bool {
+    let mut current = *window;
+    for _ in 0..MAX_TRANSIENT_DEPTH {
+        match get_property::<c_ulong>(display, &current, "WM_TRANSIENT_FOR").first() {
+            Some(id) if *id == parent.as_raw() => return true,
+            Some(id) if *id != 0 && *id != current.as_raw() => {
+                current = Window::from_raw(display, *id)
+            }
+            _ => return false,
+        }
+    }
+    false
+}
+
+/// Returns the smallest rect containing both rects.
+fn union(a: &Rect, b: &Rect) -> Rect {
+    let x = a.x.min(b.x);
+    let y = a.y.min(b.y);
+    let right = (a.x + a.width as i32).max(b.x + b.width as i32);
+    let bottom = (a.y + a.height as i32).max(b.y + b.height as i32);
+    Rect {
+        x,
+        y,
+        width: (right - x) as u32,
+        height: (bottom - y) as u32,
+    }
+}
+
+/// Checks whether the rects overlap.
+fn overlaps(a: &Rect, b: &Rect) -> bool {
+    a.x < b.x + b.width as i32
+        && b.x < a.x + a.width as i32
+        && a.y < b.y + b.height as i32
+        && b.y < a.y + a.height as i32
+}
+
+/// Returns the area covered by the given window together with its
+/// transient windows, such as dialogs, and the popups overlapping them,
+/// such as menus and tooltips. The area is relative to the root, and may
+/// extend beyond the screen.
+pub fn transient_rect(display: &Display, window: &Window) -> Rect {
+    let mut rect = screen_rect(display, window);
+    for other in get_all_windows(display) {
+        if is_transient_of(display, &other, window) {
+            rect = union(&rect, &screen_rect(display, &other));
+        }
+    }
+
+    // Popups are override-redirect windows, so the window manager neither
+    // manages nor reparents them. Windows covering the whole screen, such
+    // as those of screen lockers, are not popups.
+    let root = display.default_window();
+    let screen = root.get_rect();
+    for popup in get_children(display, &root) {
+        let is_popup = popup
+            .get_attributes()
+            .is_some_and(|a| a.viewable && a.override_redirect);
+        let popup = screen_rect(display, &popup);
+
+        let fullscreen = popup.width >= screen.width && popup.height >= screen.height;
+        if is_popup && !fullscreen && overlaps(&rect, &popup) {
+            rect = union(&rect, &popup);
+        }
+    }
+    rect
+}
+
+/// Returns the part of the rect that lies on the screen, or `None` if it
+/// lies entirely outside of it.
+pub fn clip_to_screen(display: &Display, rect: &Rect) -> Option<Rect> {
+    let screen = display.default_window().get_rect();
+    let x = rect.x.max(0);
+    let y = rect.y.max(0);
+    let right = (rect.x + rect.width as i32).min(screen.width as i32);
+    let bottom = (rect.y + rect.height as i32).min(screen.height as i32);
+
+    if right <= x || bottom <= y {
+        return None;
+    }
+    Some(Rect {
+        x,
+        y,
+        width: (right - x) as u32,
+        height: (bottom - y) as u32,
+    })
+}
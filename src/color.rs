@@ -0,0 +1,65 @@
+use crate::capture::Capture;
+use crate::clients;
+use qcms::{DataType, Intent, Profile, Transform};
+use serde::{Deserialize, Serialize};
+use xlib::Display;
+
+/// How the colour profile of the display is applied to screenshots.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorProfile {
+    /// Embeds the profile, so colour-managed viewers show the colours as
+    /// they appeared on the display.
+    Embed,
+    /// Converts the colours to sRGB, for viewers without colour management.
+    Srgb,
+    /// Ignores the profile.
+    None,
+}
+
+impl ColorProfile {
+    pub const NAMES: [&'static str; 3] = ["embed", "srgb", "none"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "embed" => Some(ColorProfile::Embed),
+            "srgb" => Some(ColorProfile::Srgb),
+            "none" => Some(ColorProfile::None),
+            _ => None,
+        }
+    }
+}
+
+/// Reads the ICC profile of the monitor the capture was made on, which
+/// colour management tools store in `_ICC_PROFILE_n` on the root window for
+/// the `n`th monitor, and in `_ICC_PROFILE` for the first. Profiles that
+/// cannot be parsed are ignored.
+pub fn read_profile(display: &Display, capture: &Capture) -> Option<Vec<u8>> {
+    let index = capture.monitor.as_ref().and_then(|name| {
+        display
+            .get_monitors()
+            .iter()
+            .position(|monitor| &monitor.name == name)
+    });
+    let name = match index {
+        Some(n) if n > 0 => format!("_ICC_PROFILE_{}", n),
+        _ => String::from("_ICC_PROFILE"),
+    };
+
+    let profile = clients::get_property::<u8>(display, &display.default_window(), &name);
+    Profile::new_from_slice(&profile, false)?;
+    Some(profile)
+}
+
+/// Converts RGB pixels from the given profile to sRGB.
+pub fn to_srgb(profile: &[u8], pixels: &mut [u8]) {
+    let input = match Profile::new_from_slice(profile, false) {
+        Some(input) => input,
+        None => return,
+    };
+    let output = Profile::new_sRGB();
+
+    if let Some(transform) = Transform::new(&input, &output, DataType::RGB8, Intent::Perceptual) {
+        transform.apply(pixels);
+    }
+}
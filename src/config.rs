@@ -0,0 +1,346 @@
+use crate::color::ColorProfile;
+use crate::errors::Error;
+use crate::keymap::{self, Action};
+use crate::overlay::Style;
+use crate::screenshot::{Format, OnExists};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use toml::value::{Table, Value};
+use xlib::Display;
+
+/// The kind of capture made when no mode is given on the command line.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    Fullscreen,
+    Region,
+    Window,
+}
+
+impl Mode {
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Fullscreen => "fullscreen",
+            Mode::Region => "region",
+            Mode::Window => "window",
+        }
+    }
+}
+
+/// An RGB colour, written as `#rrggbb` in the configuration file.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Color(pub u32);
+
+impl Color {
+    /// Returns the colour as an ARGB pixel with the given opacity.
+    pub fn with_opacity(self, opacity: f64) -> u64 {
+        let alpha = (opacity.clamp(0.0, 1.0) * 255.0).round() as u64;
+        alpha << 24 | u64::from(self.0)
+    }
+}
+
+impl TryFrom<String> for Color {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let hex = value.trim_start_matches('#');
+        match u32::from_str_radix(hex, 16) {
+            Ok(color) if hex.len() == 6 => Ok(Color(color)),
+            _ => Err(format!("invalid colour `{}`, expected #rrggbb", value)),
+        }
+    }
+}
+
+impl From<Color> for String {
+    fn from(color: Color) -> Self {
+        format!("#{:06x}", color.0)
+    }
+}
+
+/// Colours and behaviour of the overlay shown while selecting.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OverlayConfig {
+    pub selection_color: Color,
+    pub selection_opacity: f64,
+    pub background_color: Color,
+    pub background_opacity: f64,
+    pub handle_color: Color,
+    /// Width of the outline drawn around the selection, in pixels.
+    pub border_width: u32,
+    /// How many times per second the overlay is redrawn while dragging.
+    pub refresh_rate: u32,
+}
+
+impl Default for OverlayConfig {
+    fn default() -> Self {
+        Self {
+            selection_color: Color(0x14_5482),
+            selection_opacity: 0.51,
+            background_color: Color(0),
+            background_opacity: 0.0,
+            handle_color: Color(0xff_ffff),
+            border_width: 0,
+            refresh_rate: 60,
+        }
+    }
+}
+
+/// X resources that set overlay settings, by name, class and the key they
+/// correspond to in the configuration file.
+const RESOURCES: &[(&str, &str, &str)] = &[
+    ("selectionColor", "SelectionColor", "selection_color"),
+    ("selectionOpacity", "SelectionOpacity", "selection_opacity"),
+    ("dimColor", "DimColor", "background_color"),
+    ("dimOpacity", "DimOpacity", "background_opacity"),
+    ("handleColor", "HandleColor", "handle_color"),
+    ("borderWidth", "BorderWidth", "border_width"),
+];
+
+impl OverlayConfig {
+    /// Reads the overlay settings from the X resource database, e.g.
+    /// `xscreen.selectionColor: #145482` in `~/.Xresources`.
+    fn resources(display: &Display) -> Table {
+        let mut table = Table::new();
+
+        for (name, class, key) in RESOURCES {
+            let name = format!("xscreen.{}", name);
+            let class = format!("Xscreen.{}", class);
+            let value = match display.get_resource(&name, &class) {
+                Some(value) => value,
+                None => continue,
+            };
+
+            // Resources are untyped, so numbers have to be told apart from
+            // colours here.
+            let value = if key.ends_with("_color") {
+                Value::String(value)
+            } else if let Ok(n) = value.parse() {
+                Value::Integer(n)
+            } else if let Ok(n) = value.parse() {
+                Value::Float(n)
+            } else {
+                Value::String(value)
+            };
+            table.insert(key.to_string(), value);
+        }
+
+        table
+    }
+}
+
+/// Returns `$XDG_CONFIG_HOME`, falling back to `$HOME/.config` if it is
+/// not set.
+fn config_home() -> Option<PathBuf> {
+    match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir)),
+        _ => Some(PathBuf::from(env::var_os("HOME")?).join(".config")),
+    }
+}
+
+/// Returns the pictures directory of the user, as set by `XDG_PICTURES_DIR`
+/// in the environment or in `user-dirs.dirs`, which xdg-user-dirs writes
+/// as e.g. `XDG_PICTURES_DIR="$HOME/Pictures"`.
+fn pictures_dir() -> Option<PathBuf> {
+    let value = match env::var("XDG_PICTURES_DIR") {
+        Ok(value) if !value.is_empty() => value,
+        _ => {
+            let contents = fs::read_to_string(config_home()?.join("user-dirs.dirs")).ok()?;
+            contents
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.starts_with('#'))
+                .filter_map(|line| line.strip_prefix("XDG_PICTURES_DIR="))
+                .next_back()?
+                .trim_matches('"')
+                .to_string()
+        }
+    };
+
+    let home = || env::var_os("HOME").map(PathBuf::from);
+    if value == "$HOME" {
+        home()
+    } else if let Some(path) = value.strip_prefix("$HOME/") {
+        Some(home()?.join(path))
+    } else if value.starts_with('/') {
+        Some(PathBuf::from(value))
+    } else {
+        // Relative paths are not allowed by the specification.
+        None
+    }
+}
+
+/// Expands a leading `~` in the given path to the home directory.
+pub fn expand_home(path: &str) -> Option<PathBuf> {
+    let home = || env::var_os("HOME").map(PathBuf::from);
+
+    if path == "~" {
+        home()
+    } else if let Some(path) = path.strip_prefix("~/") {
+        Some(home()?.join(path))
+    } else {
+        Some(PathBuf::from(path))
+    }
+}
+
+/// Merges the table `from` into `into`. Nested tables are merged, other
+/// values are replaced.
+fn merge(into: &mut Table, from: Table) {
+    for (key, value) in from {
+        match (into.get_mut(&key), value) {
+            (Some(Value::Table(inner)), Value::Table(from)) => merge(inner, from),
+            (_, value) => {
+                into.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Settings read from `$XDG_CONFIG_HOME/xscreen/config.toml`.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub mode: Mode,
+    pub format: Format,
+    /// Directory screenshots are saved in. Defaults to `XDG_PICTURES_DIR`,
+    /// or `$HOME` if that is not set.
+    pub directory: Option<String>,
+    /// Whether screenshots are saved in a `Screenshots` folder inside the
+    /// default directory.
+    pub screenshots_folder: bool,
+    /// Path of new screenshots relative to `directory`, with placeholders
+    /// such as `{date}` and `{ext}`. See `Template`.
+    pub template: String,
+    /// What to do when a screenshot would replace an existing file.
+    pub on_exists: OnExists,
+    /// Whether the details of each capture are written to `<file>.json`.
+    pub sidecar: bool,
+    /// Whether the ICC profile of the display is embedded, or used to
+    /// convert screenshots to sRGB.
+    pub color_profile: ColorProfile,
+    /// Window types the picker includes although it skips them by default,
+    /// such as `dock`.
+    pub include_types: Vec<String>,
+    /// Shell commands run after a screenshot has been saved, which receive
+    /// its path as `$1`.
+    pub post_capture: Vec<String>,
+    pub overlay: OverlayConfig,
+    /// Key bindings by action, given as keysym names such as `Escape`.
+    pub keys: BTreeMap<Action, Vec<String>>,
+    /// Mouse button bindings by action.
+    pub buttons: BTreeMap<Action, Vec<u32>>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            mode: Mode::Fullscreen,
+            format: Format::Png,
+            directory: None,
+            screenshots_folder: false,
+            template: String::from("Screenshot {date}.{ext}"),
+            on_exists: OnExists::Increment,
+            sidecar: false,
+            color_profile: ColorProfile::Embed,
+            include_types: Vec::new(),
+            post_capture: Vec::new(),
+            overlay: OverlayConfig::default(),
+            keys: keymap::default_keys(),
+            buttons: keymap::default_buttons(),
+        }
+    }
+}
+
+impl Config {
+    /// Returns the path of the configuration file.
+    pub fn path() -> Option<PathBuf> {
+        Some(config_home()?.join("xscreen").join("config.toml"))
+    }
+
+    /// Loads the settings. Defaults are overridden by X resources of the
+    /// given display, which are in turn overridden by the configuration
+    /// file. Actions that are not bound in the file keep their default
+    /// bindings.
+    pub fn load(display: Option<&Display>) -> Result<Self, Error> {
+        let mut settings = match Value::try_from(Self::default()) {
+            Ok(Value::Table(table)) => table,
+            _ => unreachable!("the default settings serialize to a table"),
+        };
+
+        if let Some(display) = display {
+            let mut resources = Table::new();
+            resources.insert(
+                String::from("overlay"),
+                Value::Table(OverlayConfig::resources(display)),
+            );
+            merge(&mut settings, resources);
+            Self::parse(settings.clone(), "X resources")?;
+        }
+
+        let path = match Self::path() {
+            Some(path) if path.is_file() => path,
+            _ => return Self::parse(settings, "X resources"),
+        };
+
+        let source = path.to_string_lossy();
+        let contents = fs::read_to_string(&path).map_err(|e| Error::io(e, &path))?;
+        let file = toml::from_str(&contents)
+            .map_err(|e| Error::InvalidConfig(format!("{}: {}", source, e)))?;
+        merge(&mut settings, file);
+
+        Self::parse(settings, &source)
+    }
+
+    /// Deserializes the merged settings, naming the source of the last
+    /// settings merged into them on failure.
+    fn parse(settings: Table, source: &str) -> Result<Self, Error> {
+        Value::Table(settings)
+            .try_into()
+            .map_err(|e| Error::InvalidConfig(format!("{}: {}", source, e)))
+    }
+
+    /// Returns the directory screenshots are saved in, expanding a leading
+    /// `~` to the home directory. Falls back to the pictures directory of
+    /// the user, and then to `$HOME`.
+    pub fn directory(&self) -> Result<PathBuf, Error> {
+        if let Some(dir) = self.directory.as_ref() {
+            return expand_home(dir).ok_or_else(|| Error::InvalidPath(dir.clone()));
+        }
+
+        let dir = pictures_dir()
+            .or_else(|| env::var_os("HOME").map(PathBuf::from))
+            .ok_or_else(|| Error::InvalidPath(String::from("$HOME")))?;
+
+        if self.screenshots_folder {
+            return Ok(dir.join("Screenshots"));
+        }
+        Ok(dir)
+    }
+
+    /// Returns the style of the overlay.
+    pub fn style(&self) -> Style {
+        let overlay = &self.overlay;
+        Style {
+            background: overlay
+                .background_color
+                .with_opacity(overlay.background_opacity),
+            foreground: overlay
+                .selection_color
+                .with_opacity(overlay.selection_opacity),
+            handles: overlay.handle_color.with_opacity(1.0),
+            border: overlay.selection_color.with_opacity(1.0),
+            border_width: overlay.border_width,
+            refresh_rate: overlay.refresh_rate,
+        }
+    }
+
+    /// Serializes the settings as TOML.
+    pub fn to_toml(&self) -> Result<String, Error> {
+        toml::to_string_pretty(self).map_err(|e| Error::InvalidConfig(e.to_string()))
+    }
+}
@@ -1,65 +1,173 @@
 use std::io;
+use std::path::Path;
+use std::process::ExitStatus;
 use std::{error, fmt};
-use xlib;
+use xlib::Rect;
+
+// Exit codes, following sysexits.h where it has a fitting code.
+const EXIT_CANCELLED: i32 = 1;
+/// Exit code of invalid command lines.
+pub const EXIT_USAGE: i32 = 64;
+const EXIT_DATAERR: i32 = 65;
+const EXIT_NOINPUT: i32 = 66;
+const EXIT_UNAVAILABLE: i32 = 69;
+const EXIT_SOFTWARE: i32 = 70;
+const EXIT_CANTCREAT: i32 = 73;
+const EXIT_IOERR: i32 = 74;
+const EXIT_CONFIG: i32 = 78;
 
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum Error {
-    ConnectionError,
+    ConnectionError(xlib::XError),
     CompositorError,
+    /// The pointer or keyboard is grabbed by another client.
+    GrabFailed(&'static str),
+    /// The screen has no 32-bit TrueColor visual for the overlay.
+    UnsupportedVisual,
     ImageError,
+    /// The area to capture is not inside the window it is captured from.
+    OutOfBounds(Rect),
+    NoMatchingWindow(String),
     InvalidRect,
-    InvalidPath,
+    InvalidPath(String),
     Cancelled,
     WindowDestroyed,
-    IOError(String),
+    InvalidConfig(String),
+    /// A command line that clap accepts, but that cannot be carried out.
+    InvalidArgument(String),
+    ActionFailed(String, ExitStatus),
+    FileExists(String),
+    PermissionDenied(String, io::Error),
+    DiskFull(String, io::Error),
+    EncodingError(Box<dyn error::Error + Send + Sync>),
+    /// An I/O error, along with the path it occurred on, if known.
+    IOError(Option<String>, io::Error),
 }
 
-impl fmt::Display for Error {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+impl Error {
+    /// Returns the name of the variant, e.g. for machine-readable output.
+    pub fn name(&self) -> &'static str {
         use Error::*;
 
-        match &self {
-            ConnectionError => write!(f, "ConnectionError"),
-            CompositorError => write!(f, "CompositorError"),
-            ImageError => write!(f, "ImageError"),
-            InvalidRect => write!(f, "InvalidRect"),
-            InvalidPath => write!(f, "InvalidPath"),
-            Cancelled => write!(f, "Aborted"),
-            WindowDestroyed => write!(f, "WindowDestroyed"),
-            IOError(_) => write!(f, "IOError"),
+        match self {
+            ConnectionError(_) => "ConnectionError",
+            CompositorError => "CompositorError",
+            GrabFailed(_) => "GrabFailed",
+            UnsupportedVisual => "UnsupportedVisual",
+            ImageError => "ImageError",
+            OutOfBounds(_) => "OutOfBounds",
+            NoMatchingWindow(_) => "NoMatchingWindow",
+            InvalidRect => "InvalidRect",
+            InvalidPath(_) => "InvalidPath",
+            Cancelled => "Cancelled",
+            WindowDestroyed => "WindowDestroyed",
+            InvalidConfig(_) => "InvalidConfig",
+            InvalidArgument(_) => "InvalidArgument",
+            ActionFailed(..) => "ActionFailed",
+            FileExists(_) => "FileExists",
+            PermissionDenied(..) => "PermissionDenied",
+            DiskFull(..) => "DiskFull",
+            EncodingError(_) => "EncodingError",
+            IOError(..) => "IOError",
+        }
+    }
+
+    /// Returns the status xscreen exits with after the error. Cancelling
+    /// a capture is not considered a failure, and exits with 1; failures
+    /// use the codes of sysexits.h.
+    pub fn exit_code(&self) -> i32 {
+        use Error::*;
+
+        match self {
+            Cancelled => EXIT_CANCELLED,
+            ConnectionError(_) | CompositorError | GrabFailed(_) | UnsupportedVisual => {
+                EXIT_UNAVAILABLE
+            }
+            InvalidRect | OutOfBounds(_) => EXIT_DATAERR,
+            NoMatchingWindow(_) | WindowDestroyed => EXIT_NOINPUT,
+            ImageError | ActionFailed(..) => EXIT_SOFTWARE,
+            InvalidPath(_) | FileExists(_) | PermissionDenied(..) => EXIT_CANTCREAT,
+            DiskFull(..) | EncodingError(_) | IOError(..) => EXIT_IOERR,
+            InvalidConfig(_) => EXIT_CONFIG,
+            InvalidArgument(_) => EXIT_USAGE,
+        }
+    }
+
+    /// Attaches the path an I/O error occurred on.
+    pub fn io(e: io::Error, path: &Path) -> Self {
+        Error::IOError(Some(path.to_string_lossy().into_owned()), e)
+    }
+
+    /// Maps an error that occurred while writing to the given path.
+    pub fn write(e: io::Error, path: &Path) -> Self {
+        let path = path.to_string_lossy().into_owned();
+
+        match e.kind() {
+            io::ErrorKind::AlreadyExists => Error::FileExists(path),
+            io::ErrorKind::PermissionDenied | io::ErrorKind::ReadOnlyFilesystem => {
+                Error::PermissionDenied(path, e)
+            }
+            io::ErrorKind::StorageFull | io::ErrorKind::QuotaExceeded => Error::DiskFull(path, e),
+            _ => Error::IOError(Some(path), e),
         }
     }
 }
 
-impl error::Error for Error {
-    fn description(&self) -> &str {
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         use Error::*;
 
         match self {
-            ConnectionError => "Failed to connect to X",
-            CompositorError => "A composite manager is required",
-            ImageError => "Unable to get frame buffer from X",
-            InvalidRect => "Invalid region: width or height cannot be 0px",
-            InvalidPath => "Invalid path",
-            Cancelled => "Operation aborted by user",
-            WindowDestroyed => "Window destroyed by external means",
-            IOError(e) => e,
+            ConnectionError(_) => write!(f, "Failed to connect to X"),
+            CompositorError => write!(f, "A composite manager is required"),
+            GrabFailed(device) => write!(f, "Unable to grab the {}", device),
+            UnsupportedVisual => write!(f, "The screen does not support 32-bit TrueColor"),
+            ImageError => write!(f, "Unable to get frame buffer from X"),
+            OutOfBounds(rect) => write!(
+                f,
+                "Region {}x{}+{}+{} lies outside of the screen",
+                rect.width, rect.height, rect.x, rect.y
+            ),
+            NoMatchingWindow(e) => write!(f, "No matching window: {}", e),
+            InvalidRect => write!(f, "Invalid region: width or height cannot be 0px"),
+            InvalidPath(path) => write!(f, "Invalid path: {}", path),
+            Cancelled => write!(f, "Operation aborted by user"),
+            WindowDestroyed => write!(f, "Window destroyed by external means"),
+            InvalidConfig(e) => write!(f, "Invalid configuration: {}", e),
+            InvalidArgument(e) => write!(f, "Invalid argument: {}", e),
+            ActionFailed(action, status) => write!(f, "`{}` failed with {}", action, status),
+            FileExists(path) => write!(f, "File already exists: {}", path),
+            PermissionDenied(path, _) => write!(f, "Permission denied: {}", path),
+            DiskFull(path, _) => write!(f, "No space left to write {}", path),
+            EncodingError(_) => write!(f, "Unable to encode the image"),
+            IOError(Some(path), _) => write!(f, "I/O error on {}", path),
+            IOError(None, _) => write!(f, "I/O error"),
         }
     }
+}
 
+impl error::Error for Error {
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
-        None
+        use Error::*;
+
+        match self {
+            ConnectionError(e) => Some(e),
+            PermissionDenied(_, e) | DiskFull(_, e) | IOError(_, e) => Some(e),
+            EncodingError(e) => Some(e.as_ref()),
+            _ => None,
+        }
     }
 }
 
 impl From<io::Error> for Error {
     fn from(e: io::Error) -> Self {
-        Error::IOError(e.to_string())
+        Error::IOError(None, e)
     }
 }
 
 impl From<xlib::XError> for Error {
-    fn from(_: xlib::XError) -> Self {
-        Error::ConnectionError
+    fn from(e: xlib::XError) -> Self {
+        Error::ConnectionError(e)
     }
 }
@@ -0,0 +1,201 @@
+use crate::capture::Capture;
+use crate::errors::Error;
+use crate::json::Json;
+use crate::screenshot::Format;
+use std::fmt::Write;
+use std::fs;
+use std::path::PathBuf;
+
+/// Width of the thumbnails in HTML indices, in CSS pixels.
+const THUMBNAIL_WIDTH: u32 = 320;
+
+/// The formats of the index written by `--all-windows`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IndexFormat {
+    Json,
+    Html,
+}
+
+impl IndexFormat {
+    pub const NAMES: [&'static str; 2] = ["json", "html"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "json" => Some(IndexFormat::Json),
+            "html" => Some(IndexFormat::Html),
+            _ => None,
+        }
+    }
+
+    fn file_name(self) -> &'static str {
+        match self {
+            IndexFormat::Json => "index.json",
+            IndexFormat::Html => "index.html",
+        }
+    }
+}
+
+/// Lists the screenshots of a session, with the windows they show.
+pub struct Index {
+    directory: PathBuf,
+    format: Format,
+    entries: Vec<(PathBuf, Capture)>,
+}
+
+impl Index {
+    /// Creates an empty index of screenshots saved in `directory`.
+    pub fn new(directory: PathBuf, format: Format) -> Self {
+        Self {
+            directory,
+            format,
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, path: PathBuf, capture: Capture) {
+        self.entries.push((path, capture));
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Describes each screenshot in the format printed by `--json`.
+    pub fn to_json(&self) -> Json {
+        Json::Array(
+            self.entries
+                .iter()
+                .map(|(path, capture)| capture.describe(path, self.format))
+                .collect(),
+        )
+    }
+
+    /// Writes the index into the directory, returning its path.
+    pub fn write(&self, format: IndexFormat) -> Result<PathBuf, Error> {
+        let path = self.directory.join(format.file_name());
+        let contents = match format {
+            IndexFormat::Json => format!("{}\n", self.to_json()),
+            IndexFormat::Html => self.to_html(),
+        };
+
+        fs::write(&path, contents).map_err(|e| Error::write(e, &path))?;
+        Ok(path)
+    }
+
+    /// Renders the index as an HTML table with a thumbnail of each
+    /// screenshot, linked relative to the index.
+    fn to_html(&self) -> String {
+        let mut html = String::from(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>xscreen</title>\n\
+             </head>\n<body>\n<table>\n<tr><th>Screenshot</th><th>Title</th><th>Class</th>\
+             <th>Geometry</th><th>File</th></tr>\n",
+        );
+
+        for (path, capture) in &self.entries {
+            let relative = path.strip_prefix(&self.directory).unwrap_or(path);
+            let name = relative.to_string_lossy();
+            let href = escape(&percent_encode(&name));
+            let window = capture.window.as_ref();
+            let title = window.and_then(|w| w.title.as_deref()).unwrap_or_default();
+            let class = window.and_then(|w| w.class.as_deref()).unwrap_or_default();
+            let rect = &capture.rect;
+
+            let _ = writeln!(
+                html,
+                "<tr><td><a href=\"{href}\"><img src=\"{href}\" width=\"{}\" alt=\"\"></a></td>\
+                 <td>{}</td><td>{}</td><td>{}x{}+{}+{}</td><td><a href=\"{href}\">{}</a></td></tr>",
+                THUMBNAIL_WIDTH.min(rect.width),
+                escape(title),
+                escape(class),
+                rect.width,
+                rect.height,
+                rect.x,
+                rect.y,
+                escape(&name),
+                href = href,
+            );
+        }
+
+        html.push_str("</table>\n</body>\n</html>\n");
+        html
+    }
+}
+
+/// Escapes text for use in HTML content and attribute values.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Percent-encodes a relative path for use in a URL, keeping slashes.
+fn percent_encode(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            byte => {
+                let _ = write!(encoded, "%{:02X}", byte);
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::WindowInfo;
+
+    #[test]
+    fn escapes_html() {
+        assert_eq!(
+            escape(r#"<a href="x">Tom & Jerry's</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&#39;s&lt;/a&gt;"
+        );
+        assert_eq!(escape("plain"), "plain");
+    }
+
+    #[test]
+    fn percent_encodes_paths() {
+        assert_eq!(percent_encode("a/b-c_d.e~f.png"), "a/b-c_d.e~f.png");
+        assert_eq!(percent_encode("my shot#1?.png"), "my%20shot%231%3F.png");
+        assert_eq!(percent_encode("é.png"), "%C3%A9.png");
+    }
+
+    #[test]
+    fn links_screenshots_relative_to_the_index() {
+        let mut capture = Capture::example();
+        capture.window = Some(WindowInfo {
+            title: Some(String::from("<b>notes</b> & more")),
+            ..capture.window.unwrap()
+        });
+        let mut index = Index::new(PathBuf::from("/shots"), Format::Png);
+        index.push(PathBuf::from("/shots/a b.png"), capture);
+
+        let html = index.to_html();
+        assert!(html.contains(r#"<img src="a%20b.png" width="320" alt="">"#));
+        assert!(html.contains("<td>&lt;b&gt;notes&lt;/b&gt; &amp; more</td>"));
+        assert!(html.contains("<td>640x480+10+20</td>"));
+        assert!(html.contains(r#"<a href="a%20b.png">a b.png</a>"#));
+    }
+
+    #[test]
+    fn parses_index_formats() {
+        for name in &IndexFormat::NAMES {
+            assert!(IndexFormat::from_name(name).is_some());
+        }
+        assert_eq!(IndexFormat::from_name("xml"), None);
+    }
+}
@@ -0,0 +1,164 @@
+use std::fmt;
+
+/// A JSON value, for the machine-readable output of xscreen.
+#[derive(Clone, Debug)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Int(i64),
+    String(String),
+    Array(Vec<Json>),
+    /// An object whose keys keep the order they were inserted in.
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn object() -> Self {
+        Json::Object(Vec::new())
+    }
+
+    /// Adds a key to an object. Has no effect on other values.
+    pub fn with<V: Into<Json>>(mut self, key: &str, value: V) -> Self {
+        if let Json::Object(entries) = &mut self {
+            entries.push((key.to_string(), value.into()));
+        }
+        self
+    }
+
+    fn write_str(f: &mut fmt::Formatter, value: &str) -> fmt::Result {
+        write!(f, "\"")?;
+        for c in value.chars() {
+            match c {
+                '"' => write!(f, "\\\"")?,
+                '\\' => write!(f, "\\\\")?,
+                '\n' => write!(f, "\\n")?,
+                '\r' => write!(f, "\\r")?,
+                '\t' => write!(f, "\\t")?,
+                c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+                c => write!(f, "{}", c)?,
+            }
+        }
+        write!(f, "\"")
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(value) => write!(f, "{}", value),
+            Json::Int(value) => write!(f, "{}", value),
+            Json::String(value) => Self::write_str(f, value),
+            Json::Array(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(entries) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    Self::write_str(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+impl From<bool> for Json {
+    fn from(value: bool) -> Self {
+        Json::Bool(value)
+    }
+}
+
+impl From<i32> for Json {
+    fn from(value: i32) -> Self {
+        Json::Int(i64::from(value))
+    }
+}
+
+impl From<u32> for Json {
+    fn from(value: u32) -> Self {
+        Json::Int(i64::from(value))
+    }
+}
+
+impl From<u64> for Json {
+    fn from(value: u64) -> Self {
+        Json::Int(value as i64)
+    }
+}
+
+impl From<&str> for Json {
+    fn from(value: &str) -> Self {
+        Json::String(value.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(value: String) -> Self {
+        Json::String(value)
+    }
+}
+
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Json::Null, Into::into)
+    }
+}
+
+impl<T: Into<Json>> From<Vec<T>> for Json {
+    fn from(values: Vec<T>) -> Self {
+        Json::Array(values.into_iter().map(Into::into).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_values() {
+        assert_eq!(Json::Null.to_string(), "null");
+        assert_eq!(Json::from(true).to_string(), "true");
+        assert_eq!(Json::from(-3).to_string(), "-3");
+        assert_eq!(Json::from(None::<u32>).to_string(), "null");
+        assert_eq!(Json::from(vec![1u32, 2]).to_string(), "[1,2]");
+        assert_eq!(Json::from(Vec::<u32>::new()).to_string(), "[]");
+    }
+
+    #[test]
+    fn keeps_the_order_of_keys() {
+        let object = Json::object()
+            .with("b", 1u32)
+            .with("a", Json::object().with("c", "d"));
+        assert_eq!(object.to_string(), r#"{"b":1,"a":{"c":"d"}}"#);
+        assert_eq!(Json::Null.with("a", 1u32).to_string(), "null");
+    }
+
+    #[test]
+    fn escapes_quotes_and_backslashes() {
+        assert_eq!(Json::from(r#"a "b" \c"#).to_string(), r#""a \"b\" \\c""#);
+        let object = Json::object().with("\"key\"", "value");
+        assert_eq!(object.to_string(), r#"{"\"key\"":"value"}"#);
+    }
+
+    #[test]
+    fn escapes_control_characters() {
+        assert_eq!(Json::from("a\nb\r\tc").to_string(), r#""a\nb\r\tc""#);
+        assert_eq!(
+            Json::from("\u{1}\u{1f}\u{7f}").to_string(),
+            r#""\u0001\u001f\u007f""#
+        );
+        assert_eq!(Json::from("é ✓").to_string(), "\"é ✓\"");
+    }
+}
@@ -0,0 +1,272 @@
+use crate::errors::Error;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::fmt;
+use std::hash::Hash;
+use x11::xlib::XKeyEvent;
+use xlib::keysym;
+
+/// An action that can be bound to keys or mouse buttons.
+// (De)serialized through `String`, as TOML tables can only have string keys.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(try_from = "String", into = "String")]
+pub enum Action {
+    /// Aborts the capture.
+    Cancel,
+    /// Captures the current selection.
+    Confirm,
+    /// Starts a selection, or picks the highlighted window.
+    Select,
+    /// Anchors a selection made with the keyboard, or moves the selection
+    /// while it is being dragged.
+    Anchor,
+    /// Highlights the next window in the picker, or the previous one while
+    /// holding Shift.
+    Next,
+    /// Highlights the window containing the highlighted sub-window.
+    Parent,
+    /// Highlights the sub-window under the pointer of the highlighted
+    /// window.
+    Child,
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Action {
+    pub const ALL: [Action; 11] = [
+        Action::Cancel,
+        Action::Confirm,
+        Action::Select,
+        Action::Anchor,
+        Action::Next,
+        Action::Parent,
+        Action::Child,
+        Action::Up,
+        Action::Down,
+        Action::Left,
+        Action::Right,
+    ];
+
+    /// Returns the name of the action as used in the configuration file.
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Cancel => "cancel",
+            Action::Confirm => "confirm",
+            Action::Select => "select",
+            Action::Anchor => "anchor",
+            Action::Next => "next",
+            Action::Parent => "parent",
+            Action::Child => "child",
+            Action::Up => "up",
+            Action::Down => "down",
+            Action::Left => "left",
+            Action::Right => "right",
+        }
+    }
+
+    /// Returns the direction of a movement action, if any.
+    pub fn direction(self) -> Option<(i32, i32)> {
+        match self {
+            Action::Up => Some((0, -1)),
+            Action::Down => Some((0, 1)),
+            Action::Left => Some((-1, 0)),
+            Action::Right => Some((1, 0)),
+            _ => None,
+        }
+    }
+}
+
+impl TryFrom<String> for Action {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        Action::ALL
+            .iter()
+            .copied()
+            .find(|action| action.name() == name)
+            .ok_or_else(|| format!("unknown action `{}`", name))
+    }
+}
+
+impl From<Action> for String {
+    fn from(action: Action) -> Self {
+        action.name().to_string()
+    }
+}
+
+const DEFAULT_KEYS: &[(Action, &[&str])] = &[
+    (Action::Cancel, &["Escape", "q"]),
+    (Action::Confirm, &["Return", "KP_Enter"]),
+    (Action::Anchor, &["space"]),
+    (Action::Next, &["Tab"]),
+    (Action::Up, &["Up"]),
+    (Action::Down, &["Down"]),
+    (Action::Left, &["Left"]),
+    (Action::Right, &["Right"]),
+];
+
+const DEFAULT_BUTTONS: &[(Action, &[u32])] = &[
+    (Action::Select, &[1]),
+    (Action::Cancel, &[3]),
+    (Action::Parent, &[4]),
+    (Action::Child, &[5]),
+];
+
+/// Returns the default key bindings.
+pub fn default_keys() -> BTreeMap<Action, Vec<String>> {
+    DEFAULT_KEYS
+        .iter()
+        .map(|(action, names)| (*action, names.iter().map(|n| n.to_string()).collect()))
+        .collect()
+}
+
+/// Returns the default mouse button bindings.
+pub fn default_buttons() -> BTreeMap<Action, Vec<u32>> {
+    DEFAULT_BUTTONS
+        .iter()
+        .map(|(action, buttons)| (*action, buttons.to_vec()))
+        .collect()
+}
+
+/// Maps keysyms and mouse buttons to the actions they are bound to.
+pub struct Keymap {
+    keys: HashMap<u64, Action>,
+    buttons: HashMap<u32, Action>,
+}
+
+impl Keymap {
+    /// Builds the keymap from the given bindings, which include the
+    /// defaults of actions that are not rebound.
+    pub fn new(
+        keys: &BTreeMap<Action, Vec<String>>,
+        buttons: &BTreeMap<Action, Vec<u32>>,
+    ) -> Result<Self, Error> {
+        let keys = resolve(keys, &default_keys(), "Key")?
+            .into_iter()
+            .map(|(name, action)| {
+                let keysym = xlib::string_to_keysym(&name)
+                    .ok_or_else(|| Error::InvalidConfig(format!("Unknown key: {}", name)))?;
+                Ok((keysym, action))
+            })
+            .collect::<Result<_, Error>>()?;
+
+        Ok(Self {
+            keys,
+            buttons: resolve(buttons, &default_buttons(), "Button")?,
+        })
+    }
+
+    /// Returns the action bound to the key of the given event, if any.
+    /// Keys are looked up without modifiers, so Shift+q is treated as q.
+    pub fn key(&self, event: &XKeyEvent) -> Option<Action> {
+        let keysym = xlib::lookup_keysym(event, 0);
+        self.keys.get(&keysym).copied()
+    }
+
+    /// Returns the action bound to the given mouse button, if any.
+    pub fn button(&self, button: u32) -> Option<Action> {
+        self.buttons.get(&button).copied()
+    }
+}
+
+/// Maps each key or button to the action it is bound to. Default bindings
+/// are applied first, so that a key or button the user binds to another
+/// action is taken from its default action. Binding the same key or button
+/// to two rebound actions is an error.
+fn resolve<T: Clone + Eq + Hash + fmt::Display>(
+    bindings: &BTreeMap<Action, Vec<T>>,
+    defaults: &BTreeMap<Action, Vec<T>>,
+    kind: &str,
+) -> Result<HashMap<T, Action>, Error> {
+    let (defaults, rebound): (Vec<_>, Vec<_>) = bindings
+        .iter()
+        .partition(|(action, values)| defaults.get(action) == Some(values));
+
+    let mut map = HashMap::new();
+    for (action, values) in defaults {
+        for value in values {
+            map.insert(value.clone(), *action);
+        }
+    }
+
+    let mut taken = HashMap::new();
+    for (action, values) in rebound {
+        for value in values {
+            if let Some(other) = taken.insert(value.clone(), *action) {
+                if other != *action {
+                    return Err(Error::InvalidConfig(format!(
+                        "{} {} is bound to both {} and {}",
+                        kind,
+                        value,
+                        other.name(),
+                        action.name()
+                    )));
+                }
+            }
+            map.insert(value.clone(), *action);
+        }
+    }
+    Ok(map)
+}
+
+/// Returns the modifier mask belonging to the key of the given event, if
+/// the key is a modifier. Modifiers cannot be rebound.
+pub fn modifier(event: &XKeyEvent) -> u32 {
+    match xlib::lookup_keysym(event, 0) as u32 {
+        keysym::XK_Shift_L | keysym::XK_Shift_R => xlib::SHIFT_MASK,
+        keysym::XK_Alt_L | keysym::XK_Alt_R | keysym::XK_Meta_L | keysym::XK_Meta_R => {
+            xlib::MOD1_MASK
+        }
+        keysym::XK_Control_L | keysym::XK_Control_R => xlib::CONTROL_MASK,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rebound_key_replaces_default() {
+        let mut keys = default_keys();
+        keys.insert(Action::Cancel, vec![String::from("space")]);
+        let keymap = Keymap::new(&keys, &default_buttons()).unwrap();
+
+        let space = xlib::string_to_keysym("space").unwrap();
+        let escape = xlib::string_to_keysym("Escape").unwrap();
+        assert_eq!(keymap.keys.get(&space), Some(&Action::Cancel));
+        assert_eq!(keymap.keys.get(&escape), None);
+    }
+
+    #[test]
+    fn rebound_button_replaces_default() {
+        let mut buttons = default_buttons();
+        buttons.insert(Action::Cancel, vec![1]);
+        let keymap = Keymap::new(&default_keys(), &buttons).unwrap();
+
+        assert_eq!(keymap.button(1), Some(Action::Cancel));
+        assert_eq!(keymap.button(3), None);
+    }
+
+    #[test]
+    fn conflicting_bindings_are_rejected() {
+        let mut keys = default_keys();
+        keys.insert(Action::Cancel, vec![String::from("x")]);
+        keys.insert(Action::Confirm, vec![String::from("x")]);
+
+        match Keymap::new(&keys, &default_buttons()) {
+            Err(Error::InvalidConfig(message)) => assert!(message.contains("Key x")),
+            _ => panic!("conflicting bindings were accepted"),
+        }
+    }
+
+    #[test]
+    fn unknown_key_is_rejected() {
+        let mut keys = default_keys();
+        keys.insert(Action::Cancel, vec![String::from("NotAKey")]);
+        assert!(Keymap::new(&keys, &default_buttons()).is_err());
+    }
+}
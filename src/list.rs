@@ -0,0 +1,202 @@
+use crate::clients::{self, Visibility};
+use crate::json::Json;
+use xlib::{Display, Rect, Window};
+
+/// Everything xscreen knows about a client, as printed by `list-windows`.
+pub struct WindowListing {
+    pub id: u64,
+    pub title: Option<String>,
+    pub class: Option<String>,
+    pub pid: Option<u32>,
+    /// `None` if the window is shown on all desktops.
+    pub desktop: Option<u32>,
+    pub kind: String,
+    /// Position relative to the root window, and size.
+    pub rect: Rect,
+    pub depth: Option<i32>,
+    pub visibility: Visibility,
+}
+
+impl WindowListing {
+    pub fn new(display: &Display, window: &Window) -> Self {
+        Self {
+            id: window.as_raw(),
+            title: clients::title(display, window),
+            class: clients::class(display, window),
+            pid: clients::pid(display, window),
+            desktop: clients::desktop(display, window),
+            kind: clients::window_type(display, window),
+            rect: clients::screen_rect(display, window),
+            depth: window.get_attributes().map(|a| a.depth),
+            visibility: clients::visibility(display, window),
+        }
+    }
+
+    /// Lists all clients from top to bottom.
+    pub fn all(display: &Display) -> Vec<Self> {
+        clients::get_clients(display)
+            .iter()
+            .map(|window| Self::new(display, window))
+            .collect()
+    }
+
+    pub fn to_json(&self) -> Json {
+        let rect = Json::object()
+            .with("x", self.rect.x)
+            .with("y", self.rect.y)
+            .with("width", self.rect.width)
+            .with("height", self.rect.height);
+
+        Json::object()
+            .with("id", self.id)
+            .with("title", self.title.clone())
+            .with("class", self.class.clone())
+            .with("pid", self.pid)
+            .with("desktop", self.desktop)
+            .with("type", self.kind.as_str())
+            .with("rect", rect)
+            .with("depth", self.depth)
+            .with("visibility", self.visibility.name())
+    }
+
+    /// Returns the columns of the window in a table, as shown by `table`.
+    fn columns(&self) -> [String; 9] {
+        let rect = &self.rect;
+        let optional = |value: Option<String>| value.unwrap_or_else(|| String::from("-"));
+
+        [
+            format!("{:#x}", self.id),
+            optional(self.pid.map(|pid| pid.to_string())),
+            optional(self.desktop.map(|desktop| desktop.to_string())),
+            self.kind.clone(),
+            format!("{}x{}+{}+{}", rect.width, rect.height, rect.x, rect.y),
+            optional(self.depth.map(|depth| depth.to_string())),
+            self.visibility.name().to_string(),
+            optional(self.class.clone()),
+            optional(self.title.clone()),
+        ]
+    }
+}
+
+/// Formats the windows as a table with a header, in columns separated by
+/// two spaces. The title comes last, as it is the longest.
+pub fn table(windows: &[WindowListing]) -> String {
+    let header = [
+        "XID",
+        "PID",
+        "DESKTOP",
+        "TYPE",
+        "RECT",
+        "DEPTH",
+        "VISIBILITY",
+        "CLASS",
+        "TITLE",
+    ]
+    .map(String::from);
+    let rows: Vec<_> = std::iter::once(header)
+        .chain(windows.iter().map(WindowListing::columns))
+        .collect();
+
+    let mut widths = [0; 9];
+    for row in &rows {
+        for (width, column) in widths.iter_mut().zip(row) {
+            *width = (*width).max(column.chars().count());
+        }
+    }
+
+    let mut table = String::new();
+    for row in &rows {
+        let line = row
+            .iter()
+            .zip(&widths)
+            .map(|(column, width)| format!("{:1$}", column, width))
+            .collect::<Vec<_>>()
+            .join("  ");
+        table.push_str(line.trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terminal() -> WindowListing {
+        WindowListing {
+            id: 0x1e0_0003,
+            title: Some(String::from("Terminal")),
+            class: Some(String::from("Alacritty")),
+            pid: Some(42),
+            desktop: Some(0),
+            kind: String::from("normal"),
+            rect: Rect {
+                x: 10,
+                y: 20,
+                width: 640,
+                height: 480,
+            },
+            depth: Some(24),
+            visibility: Visibility::Visible,
+        }
+    }
+
+    fn dock() -> WindowListing {
+        WindowListing {
+            id: 0x40_0001,
+            title: None,
+            class: None,
+            pid: None,
+            desktop: None,
+            kind: String::from("dock"),
+            rect: Rect {
+                x: 0,
+                y: 0,
+                width: 1920,
+                height: 30,
+            },
+            depth: None,
+            visibility: Visibility::Unmapped,
+        }
+    }
+
+    #[test]
+    fn aligns_columns() {
+        let expected = "\
+XID        PID  DESKTOP  TYPE    RECT           DEPTH  VISIBILITY  CLASS      TITLE
+0x1e00003  42   0        normal  640x480+10+20  24     visible     Alacritty  Terminal
+0x400001   -    -        dock    1920x30+0+0    -      unmapped    -          -
+";
+        assert_eq!(table(&[terminal(), dock()]), expected);
+    }
+
+    #[test]
+    fn aligns_non_ascii_values() {
+        let mut window = terminal();
+        window.class = Some(String::from("Évince"));
+        let table = table(&[window, dock()]);
+        let lines: Vec<_> = table.lines().collect();
+
+        // Where the title starts, in characters.
+        let column = |line: &str, title: &str| line.chars().count() - title.len();
+        assert_eq!(column(lines[0], "TITLE"), column(lines[1], "Terminal"));
+    }
+
+    #[test]
+    fn lists_only_the_header_without_windows() {
+        assert_eq!(
+            table(&[]),
+            "XID  PID  DESKTOP  TYPE  RECT  DEPTH  VISIBILITY  CLASS  TITLE\n"
+        );
+    }
+
+    #[test]
+    fn describes_windows_as_json() {
+        assert_eq!(
+            dock().to_json().to_string(),
+            "{\"id\":4194305,\"title\":null,\"class\":null,\"pid\":null,\"desktop\":null,\
+             \"type\":\"dock\",\"rect\":{\"x\":0,\"y\":0,\"width\":1920,\"height\":30},\
+             \"depth\":null,\"visibility\":\"unmapped\"}"
+        );
+    }
+}
@@ -1,30 +1,50 @@
-// Although std::env::home_dir() is deprecated, it functions as you'd expect
-// for Unix environments. AFAIK it was deprecated because the functionality
-// in Windows was not what you'd expect.
-
-#![allow(deprecated)]
 extern crate chrono;
 extern crate clap;
 extern crate image;
+extern crate libc;
 extern crate xlib;
 
+mod capture;
+mod clients;
+mod color;
+mod config;
 mod errors;
+mod index;
+mod json;
+mod keymap;
+mod list;
+mod metadata;
 mod overlay;
 mod region;
 mod screenshot;
+mod template;
 mod window;
 
-use chrono::Local;
-use clap::{App, Arg};
+use capture::Capture;
+use clap::{App, Arg, ArgMatches, ErrorKind, SubCommand};
+use color::ColorProfile;
+use config::{Config, Mode};
 use errors::Error;
+use index::{Index, IndexFormat};
+use json::Json;
+use keymap::Keymap;
+use list::WindowListing;
 use region::Region;
-use screenshot::Screenshot;
-use std::error::Error as _;
-use std::path::PathBuf;
+use screenshot::{Format, OnExists, Screenshot};
+use std::ffi::CString;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+use std::thread;
 use std::time::Duration;
-use std::{env, thread};
+use template::Template;
 use window::WindowCapture;
-use xlib::Display;
+use xlib::{Display, Window};
+
+/// Template of the files written by `--all-windows`, unless one is given.
+const ALL_WINDOWS_TEMPLATE: &str = "{wm_class}-{id}.{ext}";
 
 /// Checks if a compositor is present
 fn has_compositor(display: &Display) -> Result<(), Error> {
@@ -37,31 +57,285 @@ fn has_compositor(display: &Display) -> Result<(), Error> {
 
 /// Sleeps for the specified duration before resuming execution
 fn delay(matches: Option<&str>) {
-    if let Some(dur) = matches {
-        let secs = dur.parse().expect("Invalid duration");
+    if let Some(secs) = matches.and_then(|dur| dur.parse().ok()) {
         let duration = Duration::from_secs(secs);
         thread::sleep(duration);
     }
 }
 
-/// Determines whether the given string is a valid filename or filepath.
-/// Generates a filename if necessary.
-fn filename(matches: Option<&str>) -> Result<PathBuf, Error> {
-    let mut path = match matches {
-        Some(p) => PathBuf::from(p),
-        None => env::home_dir().ok_or(Error::InvalidPath)?,
+/// Parses a pair of non-zero numbers separated by `separator`, such as an
+/// aspect ratio (`16:9`) or a size (`1280x720`)
+fn pair(value: &str, separator: char) -> Option<(u32, u32)> {
+    let mut parts = value.splitn(2, separator).map(|n| n.parse::<u32>());
+
+    match (parts.next(), parts.next()) {
+        (Some(Ok(a)), Some(Ok(b))) if a > 0 && b > 0 => Some((a, b)),
+        _ => None,
+    }
+}
+
+/// Validates an aspect ratio given on the command line.
+fn is_ratio(value: String) -> Result<(), String> {
+    pair(&value, ':')
+        .map(|_| ())
+        .ok_or_else(|| format!("expected two non-zero numbers such as 16:9, got `{}`", value))
+}
+
+/// Validates a size given on the command line.
+fn is_size(value: String) -> Result<(), String> {
+    pair(&value, 'x')
+        .map(|_| ())
+        .ok_or_else(|| format!("expected two non-zero numbers such as 1280x720, got `{}`", value))
+}
+
+/// Validates a number of pixels given on the command line.
+fn is_pixels(value: String) -> Result<(), String> {
+    match value.parse::<u32>() {
+        Ok(n) if n > 0 => Ok(()),
+        _ => Err(format!("expected a non-zero number of pixels, got `{}`", value)),
+    }
+}
+
+/// Validates a delay given on the command line.
+fn is_seconds(value: String) -> Result<(), String> {
+    value
+        .parse::<u64>()
+        .map(|_| ())
+        .map_err(|_| format!("expected a whole number of seconds, got `{}`", value))
+}
+
+/// Finds the window with the given ID, in hexadecimal with a `0x` prefix
+/// as printed by `list-windows`, or in decimal.
+fn window_id(display: &Display, id: &str) -> Result<Window, Error> {
+    let parsed = match id.strip_prefix("0x").or_else(|| id.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => id.parse(),
     };
+    let window = parsed.map(|id| Window::from_raw(display, id));
 
-    if path.is_dir() {
-        let time = Local::now()
-            .format("Screenshot %Y-%m-%d %H-%M-%S.png")
-            .to_string();
-        path.push(time);
+    match window {
+        Ok(window) if window.get_attributes().is_some() => Ok(window),
+        _ => Err(Error::NoMatchingWindow(format!("no window with ID {}", id))),
     }
+}
 
-    path = path.canonicalize().unwrap_or(path);
+/// Applies the settings given on the command line to the configuration.
+/// Fails if the format is taken from an output file with an unknown
+/// extension.
+fn apply_overrides(config: &mut Config, matches: &ArgMatches) -> Result<(), Error> {
+    if matches.is_present("window") || matches.is_present("window-id") {
+        config.mode = Mode::Window;
+    } else if matches.is_present("region") || matches.is_present("size") {
+        config.mode = Mode::Region;
+    } else if matches.is_present("fullscreen") {
+        config.mode = Mode::Fullscreen;
+    }
 
-    Ok(path)
+    // An explicit format takes precedence over the extension of the output.
+    let extension = matches
+        .value_of("output")
+        .map(Path::new)
+        .filter(|path| !path.is_dir())
+        .and_then(Path::extension)
+        .and_then(|ext| ext.to_str());
+
+    if let Some(format) = matches.value_of("format") {
+        config.format = Format::from_name(format).unwrap_or(config.format);
+    } else if let Some(extension) = extension {
+        config.format = Format::from_name(extension).ok_or_else(|| {
+            Error::InvalidArgument(format!(
+                "unknown image format `.{}`, choose one with --format",
+                extension
+            ))
+        })?;
+    }
+
+    if let Some(on_exists) = matches.value_of("on-exists").and_then(OnExists::from_name) {
+        config.on_exists = on_exists;
+    }
+
+    if matches.is_present("sidecar") {
+        config.sidecar = true;
+    }
+
+    if let Some(profile) = matches
+        .value_of("color-profile")
+        .and_then(ColorProfile::from_name)
+    {
+        config.color_profile = profile;
+    }
+
+    if let Some(types) = matches.values_of("include-types") {
+        config.include_types = types.map(String::from).collect();
+    }
+
+    if let Some(template) = matches.value_of("template") {
+        config.template = template.to_string();
+    }
+    Ok(())
+}
+
+/// Determines the path of the screenshot. If no file is given, the path
+/// is generated from the template, relative to the given or configured
+/// directory. Missing parent directories are created, and must be
+/// writable.
+fn filename(matches: Option<&str>, config: &Config, capture: &Capture) -> Result<PathBuf, Error> {
+    let path = match matches.map(PathBuf::from) {
+        Some(path) if !path.is_dir() => path,
+        Some(dir) => Template::new(&config.template).path(&dir, capture, config.format)?,
+        None => Template::new(&config.template).path(
+            &config.directory()?,
+            capture,
+            config.format,
+        )?,
+    };
+
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent).map_err(|e| Error::write(e, parent))?;
+
+        if !is_writable(parent) {
+            return Err(Error::InvalidPath(parent.to_string_lossy().into_owned()));
+        }
+    }
+
+    Ok(path.canonicalize().unwrap_or(path))
+}
+
+/// Checks whether the current user may create files in the given
+/// directory.
+fn is_writable(dir: &Path) -> bool {
+    let path = match CString::new(dir.as_os_str().as_bytes()) {
+        Ok(path) => path,
+        Err(_) => return false,
+    };
+    unsafe { libc::access(path.as_ptr(), libc::W_OK | libc::X_OK) == 0 }
+}
+
+/// Runs the post-capture actions in order, passing them the path of the
+/// screenshot as `$1`. Stops at the first action that fails.
+fn post_capture(actions: &[String], path: &Path) -> Result<(), Error> {
+    for action in actions {
+        let status = Command::new("sh")
+            .arg("-c")
+            .arg(action)
+            .arg("xscreen")
+            .arg(path)
+            .status()?;
+
+        if !status.success() {
+            return Err(Error::ActionFailed(action.clone(), status));
+        }
+    }
+    Ok(())
+}
+
+/// Formats the label of a result banner in bold and the given ANSI colour,
+/// unless stdout is not a terminal.
+fn banner(label: &str, color: u8) -> String {
+    if io::stdout().is_terminal() {
+        format!("\x1b[1;{}m{}\x1b[0m", color, label)
+    } else {
+        label.to_string()
+    }
+}
+
+/// Prints an error along with its causes, as JSON with `--json`.
+fn report_error(e: &Error, json: bool) {
+    let mut message = e.to_string();
+    let mut source = std::error::Error::source(e);
+    while let Some(cause) = source {
+        message = format!("{}: {}", message, cause);
+        source = cause.source();
+    }
+
+    if json {
+        let output = Json::object()
+            .with("error", e.name())
+            .with("message", message)
+            .with("exit_code", e.exit_code());
+        println!("{}", output);
+    } else {
+        println!("    {} {}: {}", banner("Error", 31), e.name(), message);
+    }
+}
+
+/// Prints the location of the configuration file, or the effective
+/// settings with `--print`. The settings are validated as they would be
+/// for a capture.
+fn print_config(matches: &ArgMatches, config_matches: &ArgMatches) -> Result<(), Error> {
+    if !config_matches.is_present("print") {
+        let path = Config::path().ok_or_else(|| Error::InvalidPath(String::from("$HOME")))?;
+        println!("{}", path.to_string_lossy());
+        return Ok(());
+    }
+
+    // X resources are only included when a display is available.
+    let display = Display::connect(None).ok();
+    let mut config = Config::load(display.as_ref())?;
+    apply_overrides(&mut config, matches)?;
+    Keymap::new(&config.keys, &config.buttons)?;
+    print!("{}", config.to_toml()?);
+    Ok(())
+}
+
+/// Prints all clients along with their geometry and state, as a table or
+/// as JSON with `--json`.
+fn list_windows(json: bool) -> Result<(), Error> {
+    let display = Display::connect(None)?;
+    display.ignore_errors();
+    let windows = WindowListing::all(&display);
+
+    if json {
+        let output = windows.iter().map(WindowListing::to_json).collect();
+        println!("{}", Json::Array(output));
+    } else {
+        print!("{}", list::table(&windows));
+    }
+    Ok(())
+}
+
+/// Captures each window the picker offers into a file of its own in the
+/// given directory, and writes an index of them. Windows that cannot be
+/// captured, e.g. because they are off-screen, are skipped. Files are named
+/// after their window unless `--template` is given, and never replace each
+/// other.
+fn capture_all(matches: &ArgMatches, dir: &str) -> Result<(PathBuf, Index), Error> {
+    let display = Display::connect(None)?;
+    display.ignore_errors();
+    let mut config = Config::load(Some(&display))?;
+    apply_overrides(&mut config, matches)?;
+    if !matches.is_present("template") {
+        config.template = String::from(ALL_WINDOWS_TEMPLATE);
+    }
+
+    let invalid = || Error::InvalidPath(dir.to_string());
+    fs::create_dir_all(dir).map_err(|_| invalid())?;
+    let directory = fs::canonicalize(dir).map_err(|_| invalid())?;
+
+    let mut index = Index::new(directory, config.format);
+    for window in clients::pickable_windows(&display, &config.include_types) {
+        let capture = Capture::window(&display, &window);
+        let screenshot = match Screenshot::window(&display, &window) {
+            Ok(screenshot) => screenshot,
+            Err(Error::ImageError) | Err(Error::OutOfBounds(_)) => continue,
+            Err(e) => return Err(e),
+        };
+
+        let profile = match config.color_profile {
+            ColorProfile::None => None,
+            _ => color::read_profile(&display, &capture),
+        };
+        let screenshot = screenshot.profile(profile, config.color_profile == ColorProfile::Srgb);
+        let path = filename(Some(dir), &config, &capture)?;
+        let path = screenshot.save(&path, config.format, OnExists::Increment, &capture)?;
+        index.push(path, capture);
+    }
+
+    let format = matches
+        .value_of("index")
+        .and_then(IndexFormat::from_name)
+        .unwrap_or(IndexFormat::Json);
+    Ok((index.write(format)?, index))
 }
 
 fn main() {
@@ -69,13 +343,94 @@ fn main() {
         .version("0.3")
         .author("Bruflot <git@bruflot.com>")
         .about("Simple X11 screenshot utility")
+        .arg(
+            Arg::with_name("all-windows")
+                .long("all-windows")
+                .value_name("DIR")
+                .help("Captures every window into a file of its own, with an index")
+                .conflicts_with_all(&["window", "region", "fullscreen", "size", "output"]),
+        )
+        .arg(
+            Arg::with_name("color-profile")
+                .long("color-profile")
+                .value_name("PROFILE")
+                .possible_values(&ColorProfile::NAMES)
+                .help("How the ICC profile of the display is applied"),
+        )
         .arg(
             Arg::with_name("delay")
                 .short("d")
                 .long("delay")
                 .value_name("SECONDS")
+                .validator(is_seconds)
                 .help("Delay the screenshot by the specified duration")
-                .conflicts_with_all(&["window", "region"]),
+                .conflicts_with_all(&["window", "region", "size"]),
+        )
+        .arg(
+            Arg::with_name("aspect")
+                .long("aspect")
+                .value_name("W:H")
+                .validator(is_ratio)
+                .help("Aspect ratio the region is locked to while holding Shift")
+                .conflicts_with("window"),
+        )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .value_name("FORMAT")
+                .possible_values(&Format::NAMES)
+                .help("Image format of the screenshot"),
+        )
+        .arg(
+            Arg::with_name("fullscreen")
+                .short("f")
+                .long("fullscreen")
+                .help("Captures the entire screen")
+                .conflicts_with_all(&["window", "region", "size"]),
+        )
+        .arg(
+            Arg::with_name("index")
+                .long("index")
+                .value_name("FORMAT")
+                .possible_values(&IndexFormat::NAMES)
+                .requires("all-windows")
+                .help("Format of the index written by --all-windows"),
+        )
+        .arg(
+            Arg::with_name("json")
+                .long("json")
+                .help("Prints the result as JSON"),
+        )
+        .arg(
+            Arg::with_name("include-types")
+                .long("include-types")
+                .value_name("TYPES")
+                .use_delimiter(true)
+                .possible_values(&clients::EXCLUDED_TYPES)
+                .help("Includes windows of these types in the picker"),
+        )
+        .arg(
+            Arg::with_name("max-size")
+                .long("max-size")
+                .value_name("WxH")
+                .validator(is_size)
+                .help("Largest region that can be selected")
+                .conflicts_with("window"),
+        )
+        .arg(
+            Arg::with_name("min-size")
+                .long("min-size")
+                .value_name("WxH")
+                .validator(is_size)
+                .help("Smallest region that can be selected")
+                .conflicts_with("window"),
+        )
+        .arg(
+            Arg::with_name("on-exists")
+                .long("on-exists")
+                .value_name("POLICY")
+                .possible_values(&OnExists::NAMES)
+                .help("What to do when the file already exists"),
         )
         .arg(
             Arg::with_name("region")
@@ -84,6 +439,40 @@ fn main() {
                 .help("Captures a region of the screen")
                 .conflicts_with("window"),
         )
+        .arg(
+            Arg::with_name("adjust")
+                .short("a")
+                .long("adjust")
+                .help("Keeps the region open for adjustments until confirmed with Enter")
+                .conflicts_with("window"),
+        )
+        .arg(
+            Arg::with_name("sidecar")
+                .long("sidecar")
+                .help("Writes the details of the capture to <file>.json"),
+        )
+        .arg(
+            Arg::with_name("size")
+                .long("size")
+                .value_name("WxH")
+                .validator(is_size)
+                .help("Captures a region of a fixed size, placed by clicking")
+                .conflicts_with_all(&["window", "min-size", "max-size"]),
+        )
+        .arg(
+            Arg::with_name("snap")
+                .long("snap")
+                .value_name("PIXELS")
+                .validator(is_pixels)
+                .help("Rounds the origin and size of the region to multiples of the given value")
+                .conflicts_with("window"),
+        )
+        .arg(
+            Arg::with_name("template")
+                .long("template")
+                .value_name("TEMPLATE")
+                .help("Template of the path of the screenshot, relative to <output>"),
+        )
         .arg(
             Arg::with_name("window")
                 .short("w")
@@ -91,42 +480,190 @@ fn main() {
                 .help("Captures a specific window")
                 .conflicts_with("region"),
         )
+        .arg(
+            Arg::with_name("window-id")
+                .long("window-id")
+                .value_name("XID")
+                .help("Captures the window with the given ID, as printed by list-windows")
+                .conflicts_with_all(&["window", "region", "fullscreen", "size", "all-windows"]),
+        )
+        .arg(
+            Arg::with_name("with-transients")
+                .long("with-transients")
+                .help("Captures the window together with its dialogs and open menus")
+                .conflicts_with_all(&["region", "fullscreen", "size"]),
+        )
         .arg(
             Arg::with_name("output")
                 .help(
                     "Specifies the file or directory in which the screenshot will be saved.\n\
-                     Default is $HOME.",
+                     Default is XDG_PICTURES_DIR, or $HOME.",
                 )
                 .index(1),
         )
-        .get_matches();
+        .subcommand(
+            SubCommand::with_name("config")
+                .about("Shows the location of the configuration file")
+                .arg(
+                    Arg::with_name("print")
+                        .long("print")
+                        .help("Prints the effective settings instead"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("list-windows")
+                .about("Lists the windows along with their geometry and state")
+                .arg(
+                    Arg::with_name("json")
+                        .long("json")
+                        .help("Prints the windows as JSON"),
+                ),
+        )
+        .get_matches_safe()
+        .unwrap_or_else(|e| match e.kind {
+            ErrorKind::HelpDisplayed | ErrorKind::VersionDisplayed => e.exit(),
+            // clap exits with 1 by default, which is reserved for cancelled
+            // captures.
+            _ => {
+                eprintln!("{}", e.message);
+                process::exit(errors::EXIT_USAGE);
+            }
+        });
+
+    if let Some(config_matches) = matches.subcommand_matches("config") {
+        if let Err(e) = print_config(&matches, config_matches) {
+            report_error(&e, false);
+            process::exit(e.exit_code());
+        }
+        return;
+    }
+
+    if let Some(list_matches) = matches.subcommand_matches("list-windows") {
+        let json = matches.is_present("json") || list_matches.is_present("json");
+        if let Err(e) = list_windows(json) {
+            report_error(&e, json);
+            process::exit(e.exit_code());
+        }
+        return;
+    }
 
     delay(matches.value_of("delay"));
 
+    let json = matches.is_present("json");
+    if let Some(dir) = matches.value_of("all-windows") {
+        match capture_all(&matches, dir) {
+            Ok((path, index)) if json => {
+                let output = Json::object()
+                    .with("index", path.to_string_lossy().into_owned())
+                    .with("windows", index.to_json());
+                println!("{}", output);
+            }
+            Ok((path, index)) => println!(
+                "    {} Saved {} windows, indexed in {}",
+                banner("Success", 32),
+                index.len(),
+                path.to_string_lossy()
+            ),
+            Err(e) => {
+                report_error(&e, json);
+                process::exit(e.exit_code());
+            }
+        }
+        return;
+    }
+
     let result = || -> Result<_, Error> {
-        let path = filename(matches.value_of("output"))?;
         let display = Display::connect(None)?;
-        let _ = has_compositor(&display)?;
-
-        let screenshot = if matches.is_present("window") {
-            let window = WindowCapture::new(&display).show()?;
-            Screenshot::window(&display, &window)
-        } else if matches.is_present("region") {
-            let rect = Region::new(&display).show()?;
-            Screenshot::with_rect(&display, &display.default_window(), rect)
-        } else {
-            Screenshot::fullscreen(&display)
+        display.ignore_errors();
+        let mut config = Config::load(Some(&display))?;
+        apply_overrides(&mut config, &matches)?;
+        let keymap = Keymap::new(&config.keys, &config.buttons)?;
+        has_compositor(&display)?;
+        // Report an invalid directory before the user selects anything.
+        if matches.value_of("output").is_none() {
+            config.directory()?;
+        }
+
+        let (screenshot, capture) = match config.mode {
+            Mode::Window => {
+                let window = match matches.value_of("window-id") {
+                    Some(id) => window_id(&display, id)?,
+                    None => WindowCapture::new(&display, &keymap, config.style())?
+                        .include_types(&config.include_types)
+                        .show()?,
+                };
+
+                if matches.is_present("with-transients") {
+                    let rect = clients::transient_rect(&display, &window);
+                    let rect = clients::clip_to_screen(&display, &rect)
+                        .ok_or(Error::OutOfBounds(rect))?;
+                    (
+                        Screenshot::with_rect(&display, &display.default_window(), rect),
+                        Capture {
+                            rect,
+                            ..Capture::window(&display, &window)
+                        },
+                    )
+                } else {
+                    (
+                        Screenshot::window(&display, &window),
+                        Capture::window(&display, &window),
+                    )
+                }
+            }
+            Mode::Region => {
+                let rect = Region::new(&display, &keymap, config.style())?
+                    .adjustable(matches.is_present("adjust"))
+                    .aspect(matches.value_of("aspect").and_then(|v| pair(v, ':')))
+                    .size(matches.value_of("size").and_then(|v| pair(v, 'x')))
+                    .min_size(matches.value_of("min-size").and_then(|v| pair(v, 'x')))
+                    .max_size(matches.value_of("max-size").and_then(|v| pair(v, 'x')))
+                    .snap(matches.value_of("snap").and_then(|n| n.parse().ok()))
+                    .show()?;
+                (
+                    Screenshot::with_rect(&display, &display.default_window(), rect),
+                    Capture::new(&display, Mode::Region, rect),
+                )
+            }
+            Mode::Fullscreen => {
+                let root = clients::screen_rect(&display, &display.default_window());
+                (
+                    Screenshot::fullscreen(&display),
+                    Capture::new(&display, Mode::Fullscreen, root),
+                )
+            }
+        };
+
+        let profile = match config.color_profile {
+            ColorProfile::None => None,
+            _ => color::read_profile(&display, &capture),
         };
+        let screenshot = screenshot?.profile(profile, config.color_profile == ColorProfile::Srgb);
+        let path = filename(matches.value_of("output"), &config, &capture)?;
+        let path = screenshot.save(&path, config.format, config.on_exists, &capture)?;
+
+        if config.sidecar {
+            let mut sidecar = path.clone().into_os_string();
+            sidecar.push(".json");
+            let sidecar = PathBuf::from(sidecar);
+            fs::write(&sidecar, format!("{}\n", capture.describe(&path, config.format)))
+                .map_err(|e| Error::write(e, &sidecar))?;
+        }
 
-        screenshot?.save(&path)?;
-        Ok(path)
+        post_capture(&config.post_capture, &path)?;
+        Ok((path, config.format, capture))
     };
 
     match result() {
-        Ok(path) => println!(
-            "    \x1b[1;32mSuccess\x1b[0m Saved to {}",
+        Ok((path, format, capture)) if json => println!("{}", capture.describe(&path, format)),
+        Ok((path, _, _)) => println!(
+            "    {} Saved to {}",
+            banner("Success", 32),
             path.to_string_lossy()
         ),
-        Err(e) => println!("    \x1b[1;31mError\x1b[0m {}: {}", e, e.description()),
+        Err(e) => {
+            report_error(&e, json);
+            process::exit(e.exit_code());
+        }
     }
 }
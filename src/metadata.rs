@@ -0,0 +1,400 @@
+use crate::capture::Capture;
+
+/// Chunk type of uncompressed Latin-1 text in PNG.
+const TEXT: [u8; 4] = *b"tEXt";
+/// Chunk type of (optionally compressed) UTF-8 text in PNG.
+const ITXT: [u8; 4] = *b"iTXt";
+
+/// Chunk type of an embedded ICC profile in PNG.
+const ICCP: [u8; 4] = *b"iCCP";
+/// Chunk type marking PNG images as sRGB.
+const SRGB: [u8; 4] = *b"sRGB";
+
+/// JPEG marker of the APP1 segment, which holds EXIF data.
+pub const APP1: u8 = 0xe1;
+/// JPEG marker of the APP2 segment, which holds ICC profiles.
+pub const APP2: u8 = 0xe2;
+
+/// Largest part of an ICC profile that fits into a single APP2 segment,
+/// after the `ICC_PROFILE` identifier and the sequence numbers.
+const ICC_SEGMENT_SIZE: usize = 65_519;
+/// Most APP2 segments a profile can be split into, as their sequence
+/// numbers are single bytes.
+const MAX_ICC_SEGMENTS: usize = 255;
+
+// EXIF tags and field types.
+const IMAGE_DESCRIPTION: u16 = 0x010e;
+const SOFTWARE: u16 = 0x0131;
+const DATE_TIME: u16 = 0x0132;
+const EXIF_IFD: u16 = 0x8769;
+const EXIF_VERSION: u16 = 0x9000;
+const DATE_TIME_ORIGINAL: u16 = 0x9003;
+const USER_COMMENT: u16 = 0x9286;
+const ASCII: u16 = 2;
+const LONG: u16 = 4;
+const UNDEFINED: u16 = 7;
+
+/// Returns the PNG text chunks describing the capture. Values that are
+/// not plain ASCII are stored as UTF-8 in `iTXt` chunks, as `tEXt` only
+/// supports Latin-1.
+pub fn png_chunks(capture: &Capture) -> Vec<([u8; 4], Vec<u8>)> {
+    capture
+        .metadata()
+        .into_iter()
+        .map(|(keyword, value)| {
+            let mut data = keyword.as_bytes().to_vec();
+            data.push(0);
+
+            if value.is_ascii() {
+                data.extend_from_slice(value.as_bytes());
+                (TEXT, data)
+            } else {
+                // Uncompressed, without language tag or translated keyword.
+                data.extend_from_slice(&[0, 0, 0, 0]);
+                data.extend_from_slice(value.as_bytes());
+                (ITXT, data)
+            }
+        })
+        .collect()
+}
+
+/// Returns the PNG chunk embedding the given ICC profile, compressed with
+/// zlib as the specification requires.
+pub fn png_profile(profile: &[u8]) -> ([u8; 4], Vec<u8>) {
+    let mut data = b"ICC profile\0\0".to_vec();
+    data.extend(deflate::deflate_bytes_zlib(profile));
+    (ICCP, data)
+}
+
+/// Returns the PNG chunk marking an image as sRGB, with perceptual
+/// rendering intent.
+pub fn png_srgb() -> ([u8; 4], Vec<u8>) {
+    (SRGB, vec![0])
+}
+
+/// Returns the payloads of the APP2 segments embedding the given ICC
+/// profile in JPEG. Profiles are split into numbered segments, as a single
+/// segment holds less than 64KiB. Returns `None` if the profile needs more
+/// segments than can be numbered.
+pub fn icc_segments(profile: &[u8]) -> Option<Vec<Vec<u8>>> {
+    let chunks: Vec<_> = profile.chunks(ICC_SEGMENT_SIZE).collect();
+    if chunks.len() > MAX_ICC_SEGMENTS {
+        return None;
+    }
+
+    let segments = chunks
+        .iter()
+        .enumerate()
+        .map(|(i, chunk)| {
+            let mut payload = b"ICC_PROFILE\0".to_vec();
+            payload.push(i as u8 + 1);
+            payload.push(chunks.len() as u8);
+            payload.extend_from_slice(chunk);
+            payload
+        })
+        .collect();
+    Some(segments)
+}
+
+/// Returns the payload of an APP1 segment with EXIF data describing the
+/// capture. The title of the window is stored as the image description if
+/// it is plain ASCII, and all metadata as the user comment.
+pub fn exif(capture: &Capture) -> Vec<u8> {
+    let date = capture.time.format("%Y:%m:%d %H:%M:%S").to_string();
+    let software = format!("xscreen {}", env!("CARGO_PKG_VERSION"));
+    let comment = capture
+        .metadata()
+        .iter()
+        .map(|(keyword, value)| format!("{}: {}", keyword, value))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let mut ifd0 = vec![
+        (SOFTWARE, ASCII, ascii(&software)),
+        (DATE_TIME, ASCII, ascii(&date)),
+        (EXIF_IFD, LONG, vec![0; 4]),
+    ];
+    let title = capture.window.as_ref().and_then(|w| w.title.as_ref());
+    if let Some(title) = title.filter(|title| title.is_ascii()) {
+        ifd0.insert(0, (IMAGE_DESCRIPTION, ASCII, ascii(title)));
+    }
+
+    // UCS-2 in the byte order of the TIFF header, i.e. little endian.
+    let mut user_comment = b"UNICODE\0".to_vec();
+    user_comment.extend(comment.encode_utf16().flat_map(u16::to_le_bytes));
+    let exif_ifd = vec![
+        (EXIF_VERSION, UNDEFINED, b"0230".to_vec()),
+        (DATE_TIME_ORIGINAL, ASCII, ascii(&date)),
+        (USER_COMMENT, UNDEFINED, user_comment),
+    ];
+
+    // The TIFF header is followed by IFD0 and then the EXIF IFD, each
+    // directly followed by the values that do not fit into their entries.
+    let ifd0_offset = 8;
+    let exif_offset = ifd0_offset + ifd_size(&ifd0);
+    let pointer = ifd0.iter_mut().find(|(tag, _, _)| *tag == EXIF_IFD);
+    if let Some((_, _, value)) = pointer {
+        *value = (exif_offset as u32).to_le_bytes().to_vec();
+    }
+
+    let mut tiff = b"II*\0".to_vec();
+    tiff.extend_from_slice(&(ifd0_offset as u32).to_le_bytes());
+    write_ifd(&mut tiff, &ifd0, 0);
+    write_ifd(&mut tiff, &exif_ifd, 0);
+
+    let mut payload = b"Exif\0\0".to_vec();
+    payload.extend(tiff);
+    payload
+}
+
+/// Encodes a string as a null-terminated EXIF ASCII value.
+fn ascii(value: &str) -> Vec<u8> {
+    let mut bytes = value.as_bytes().to_vec();
+    bytes.push(0);
+    bytes
+}
+
+/// Returns the size of an IFD including the values stored after it.
+fn ifd_size(entries: &[(u16, u16, Vec<u8>)]) -> usize {
+    let values: usize = entries
+        .iter()
+        .map(|(_, _, value)| value.len())
+        .filter(|len| *len > 4)
+        .map(|len| len + len % 2)
+        .sum();
+    2 + entries.len() * 12 + 4 + values
+}
+
+/// Appends an IFD to the TIFF data, followed by its values that are longer
+/// than four bytes. Values are padded to even offsets.
+fn write_ifd(tiff: &mut Vec<u8>, entries: &[(u16, u16, Vec<u8>)], next: u32) {
+    let mut offset = tiff.len() + 2 + entries.len() * 12 + 4;
+    let mut values = Vec::new();
+
+    tiff.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    for (tag, kind, value) in entries {
+        let count = match *kind {
+            LONG => value.len() / 4,
+            _ => value.len(),
+        };
+        tiff.extend_from_slice(&tag.to_le_bytes());
+        tiff.extend_from_slice(&kind.to_le_bytes());
+        tiff.extend_from_slice(&(count as u32).to_le_bytes());
+
+        if value.len() <= 4 {
+            let mut inline = value.clone();
+            inline.resize(4, 0);
+            tiff.extend(inline);
+        } else {
+            tiff.extend_from_slice(&(offset as u32).to_le_bytes());
+            values.extend_from_slice(value);
+            if value.len() % 2 == 1 {
+                values.push(0);
+            }
+            offset += value.len() + value.len() % 2;
+        }
+    }
+    tiff.extend_from_slice(&next.to_le_bytes());
+    tiff.extend(values);
+}
+
+/// Inserts segments into an encoded JPEG image in the given order, after
+/// the start of image marker and the JFIF header, if any. Payloads that do
+/// not fit into a segment are skipped.
+pub fn insert_jpeg_segments(jpeg: &mut Vec<u8>, segments: &[(u8, Vec<u8>)]) {
+    let mut position = 2;
+    if jpeg.get(2..4) == Some(&[0xff, 0xe0]) {
+        let length = u16::from_be_bytes([jpeg[4], jpeg[5]]) as usize;
+        position += 2 + length;
+    }
+
+    let mut bytes = Vec::new();
+    for (marker, payload) in segments {
+        if payload.len() > u16::MAX as usize - 2 {
+            continue;
+        }
+        bytes.extend_from_slice(&[0xff, *marker]);
+        bytes.extend_from_slice(&(payload.len() as u16 + 2).to_be_bytes());
+        bytes.extend_from_slice(payload);
+    }
+    jpeg.splice(position..position, bytes);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reads the entries of the IFD at `offset` in the TIFF data, along
+    /// with their values, and the offset of the next IFD.
+    fn read_ifd(tiff: &[u8], offset: usize) -> (Vec<(u16, u16, Vec<u8>)>, u32) {
+        let u16_at = |i: usize| u16::from_le_bytes([tiff[i], tiff[i + 1]]);
+        let u32_at =
+            |i: usize| u32::from_le_bytes([tiff[i], tiff[i + 1], tiff[i + 2], tiff[i + 3]]);
+
+        let count = u16_at(offset) as usize;
+        let entries = (0..count)
+            .map(|i| {
+                let entry = offset + 2 + i * 12;
+                let (tag, kind, count) = (u16_at(entry), u16_at(entry + 2), u32_at(entry + 4));
+                let len = count as usize * if kind == LONG { 4 } else { 1 };
+                let start = if len <= 4 {
+                    entry + 8
+                } else {
+                    u32_at(entry + 8) as usize
+                };
+                (tag, kind, tiff[start..start + len].to_vec())
+            })
+            .collect();
+        (entries, u32_at(offset + 2 + count * 12))
+    }
+
+    fn value(entries: &[(u16, u16, Vec<u8>)], tag: u16) -> &[u8] {
+        &entries.iter().find(|(t, _, _)| *t == tag).unwrap().2
+    }
+
+    #[test]
+    fn exif_offsets_point_at_values() {
+        let payload = exif(&Capture::example());
+        assert_eq!(&payload[..6], b"Exif\0\0");
+        let tiff = &payload[6..];
+        assert_eq!(&tiff[..8], b"II*\0\x08\0\0\0");
+
+        let (ifd0, next) = read_ifd(tiff, 8);
+        assert_eq!(next, 0);
+        assert_eq!(value(&ifd0, IMAGE_DESCRIPTION), b"Terminal\0");
+        assert_eq!(value(&ifd0, DATE_TIME), b"2019:06:01 12:00:00\0");
+        let software = format!("xscreen {}\0", env!("CARGO_PKG_VERSION"));
+        assert_eq!(value(&ifd0, SOFTWARE), software.as_bytes());
+
+        let pointer = value(&ifd0, EXIF_IFD);
+        let exif_offset = u32::from_le_bytes([pointer[0], pointer[1], pointer[2], pointer[3]]);
+        assert_eq!(
+            exif_offset as usize,
+            8 + ifd_size(&[
+                (IMAGE_DESCRIPTION, ASCII, ascii("Terminal")),
+                (SOFTWARE, ASCII, software.as_bytes().to_vec()),
+                (DATE_TIME, ASCII, ascii("2019:06:01 12:00:00")),
+                (EXIF_IFD, LONG, vec![0; 4]),
+            ])
+        );
+
+        let (exif_ifd, _) = read_ifd(tiff, exif_offset as usize);
+        assert_eq!(value(&exif_ifd, EXIF_VERSION), b"0230");
+        assert_eq!(
+            value(&exif_ifd, DATE_TIME_ORIGINAL),
+            b"2019:06:01 12:00:00\0"
+        );
+        let comment = value(&exif_ifd, USER_COMMENT);
+        assert_eq!(&comment[..8], b"UNICODE\0");
+        let text: Vec<u16> = comment[8..]
+            .chunks(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect();
+        assert!(String::from_utf16(&text)
+            .unwrap()
+            .contains("Window Class: Alacritty"));
+    }
+
+    #[test]
+    fn non_ascii_titles_are_only_in_the_user_comment() {
+        let mut capture = Capture::example();
+        if let Some(window) = capture.window.as_mut() {
+            window.title = Some(String::from("Café"));
+        }
+        let payload = exif(&capture);
+        let tiff = &payload[6..];
+
+        let (ifd0, _) = read_ifd(tiff, 8);
+        assert!(ifd0.iter().all(|(tag, _, _)| *tag != IMAGE_DESCRIPTION));
+        let pointer = value(&ifd0, EXIF_IFD);
+        let exif_offset = u32::from_le_bytes([pointer[0], pointer[1], pointer[2], pointer[3]]);
+        let (exif_ifd, _) = read_ifd(tiff, exif_offset as usize);
+        let text: Vec<u16> = value(&exif_ifd, USER_COMMENT)[8..]
+            .chunks(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect();
+        assert!(String::from_utf16(&text).unwrap().contains("Title: Café"));
+    }
+
+    #[test]
+    fn odd_values_are_padded() {
+        let entries = [
+            (SOFTWARE, ASCII, vec![b'a'; 5]),
+            (DATE_TIME, ASCII, vec![b'b'; 6]),
+        ];
+        let mut tiff = vec![0; 8];
+        write_ifd(&mut tiff, &entries, 0);
+
+        assert_eq!(tiff.len(), 8 + ifd_size(&entries));
+        let (read, _) = read_ifd(&tiff, 8);
+        assert_eq!(value(&read, SOFTWARE), b"aaaaa");
+        assert_eq!(value(&read, DATE_TIME), b"bbbbbb");
+    }
+
+    #[test]
+    fn jpeg_segments_follow_the_jfif_header() {
+        let jfif = [0xff, 0xe0, 0x00, 0x04, 0xaa, 0xbb];
+        let mut jpeg = [&[0xff, 0xd8][..], &jfif, &[0xff, 0xda]].concat();
+        insert_jpeg_segments(&mut jpeg, &[(APP1, vec![1, 2]), (APP2, vec![3])]);
+
+        let expected = [
+            &[0xff, 0xd8][..],
+            &jfif,
+            &[0xff, APP1, 0x00, 0x04, 1, 2],
+            &[0xff, APP2, 0x00, 0x03, 3],
+            &[0xff, 0xda],
+        ]
+        .concat();
+        assert_eq!(jpeg, expected);
+    }
+
+    #[test]
+    fn jpeg_segments_follow_the_start_of_image() {
+        let mut jpeg = vec![0xff, 0xd8, 0xff, 0xdb];
+        insert_jpeg_segments(&mut jpeg, &[(APP1, vec![7])]);
+        assert_eq!(jpeg, [0xff, 0xd8, 0xff, APP1, 0x00, 0x03, 7, 0xff, 0xdb]);
+    }
+
+    #[test]
+    fn oversized_jpeg_segments_are_skipped() {
+        let mut jpeg = vec![0xff, 0xd8, 0xff, 0xdb];
+        insert_jpeg_segments(&mut jpeg, &[(APP1, vec![0; 65_534]), (APP2, vec![1])]);
+        assert_eq!(jpeg, [0xff, 0xd8, 0xff, APP2, 0x00, 0x03, 1, 0xff, 0xdb]);
+    }
+
+    #[test]
+    fn icc_profiles_are_split_into_numbered_segments() {
+        let profile: Vec<u8> = (0..ICC_SEGMENT_SIZE * 2 + 10).map(|i| i as u8).collect();
+        let segments = icc_segments(&profile).unwrap();
+
+        assert_eq!(segments.len(), 3);
+        let mut joined = Vec::new();
+        for (i, segment) in segments.iter().enumerate() {
+            assert_eq!(&segment[..12], b"ICC_PROFILE\0");
+            assert_eq!(segment[12..14], [i as u8 + 1, 3]);
+            assert!(segment.len() <= u16::MAX as usize - 2);
+            joined.extend_from_slice(&segment[14..]);
+        }
+        assert_eq!(joined, profile);
+    }
+
+    #[test]
+    fn png_profiles_are_compressed() {
+        let (kind, data) = png_profile(b"profile");
+        assert_eq!(kind, ICCP);
+        assert_eq!(&data[..13], b"ICC profile\0\0");
+        // A zlib header with the default window size.
+        assert_eq!(data[13] & 0x0f, 8);
+        assert_eq!(u16::from_be_bytes([data[13], data[14]]) % 31, 0);
+    }
+
+    #[test]
+    fn oversized_icc_profiles_are_skipped() {
+        let profile = vec![0; ICC_SEGMENT_SIZE * MAX_ICC_SEGMENTS];
+        let segments = icc_segments(&profile).unwrap();
+        assert_eq!(segments[MAX_ICC_SEGMENTS - 1][12..14], [255, 255]);
+
+        let profile = vec![0; ICC_SEGMENT_SIZE * MAX_ICC_SEGMENTS + 1];
+        assert!(icc_segments(&profile).is_none());
+    }
+}
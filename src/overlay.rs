@@ -1,28 +1,51 @@
+use crate::errors::Error;
+use std::convert::TryFrom;
 use std::time::{Duration, Instant};
 use xlib::{
-    Display, Event, EventKind, GCValues, GContext, Rect, SetWindowAttributes, VisualInfo, Window,
+    Display, Event, EventKind, Font, GCValues, GContext, Rect, SetWindowAttributes, VisualInfo,
+    Window,
 };
 
-const BACKGROUND: u64 = 0; //0x82000000;
-const FOREGROUND: u64 = 0x8214_5482; // 0x73284;
-const REFRESH_RATE: u128 = 1_000_000_000 / 60;
+/// Font of the captions in the window picker.
+const CAPTION_FONT: &str = "fixed";
+/// Space between the caption and the edges of its background, in pixels.
+const CAPTION_PADDING: i32 = 4;
+/// Smallest width of the outline around windows in the picker, in pixels.
+const MIN_OUTLINE_WIDTH: u32 = 2;
+
+/// The colours of the overlay as ARGB pixels, and the rate at which it is
+/// redrawn.
+#[derive(Clone, Copy, Debug)]
+pub struct Style {
+    pub background: u64,
+    pub foreground: u64,
+    pub handles: u64,
+    pub border: u64,
+    pub border_width: u32,
+    pub refresh_rate: u32,
+}
 
 pub struct Overlay<'a> {
     pub(super) display: &'a Display,
     pub(super) root: Window,
+    pub(super) bounds: Rect,
     overlay: Window,
     gc: GContext<'a>,
+    font: Option<Font>,
+    style: Style,
+    frame: u128,
     time: Instant,
     active: bool,
 }
 
 impl<'a> Overlay<'a> {
-    pub(super) fn new(display: &'a Display) -> Self {
+    pub(super) fn new(display: &'a Display, style: Style) -> Result<Self, Error> {
         let width = display.get_width(0) as u32;
         let height = display.get_height(0) as u32;
-        let visual = VisualInfo::from(display, 0, 32, xlib::TRUE_COLOR);
+        let visual =
+            VisualInfo::from(display, 0, 32, xlib::TRUE_COLOR).ok_or(Error::UnsupportedVisual)?;
         let root = display.default_window();
-        let mut attr = Self::set_attributes(&display, &root, &visual);
+        let mut attr = Self::set_attributes(display, &root, &visual, &style);
         let rect = Rect {
             x: 0,
             y: 0,
@@ -31,7 +54,7 @@ impl<'a> Overlay<'a> {
         };
 
         let overlay = Window::new(
-            &display,
+            display,
             rect,
             32,
             visual.as_raw().visual,
@@ -45,17 +68,25 @@ impl<'a> Overlay<'a> {
         );
 
         let values = GCValues::default();
-        let gc = GContext::new(&display, &overlay, 0, values);
-        gc.set_foreground(FOREGROUND);
+        let gc = GContext::new(display, &overlay, 0, values);
+        gc.set_foreground(style.foreground);
+        let font = Font::load(display, CAPTION_FONT);
+        if let Some(font) = &font {
+            gc.set_font(font);
+        }
 
-        Self {
+        Ok(Self {
             display,
             root,
+            bounds: rect,
             overlay,
             gc,
+            font,
+            style,
+            frame: 1_000_000_000 / u128::from(style.refresh_rate.max(1)),
             time: Instant::now(),
             active: true,
-        }
+        })
     }
 
     /// Sets the attributes for the overlay window.
@@ -63,13 +94,13 @@ impl<'a> Overlay<'a> {
         display: &Display,
         window: &Window,
         visual: &VisualInfo,
+        style: &Style,
     ) -> SetWindowAttributes {
         let mut attr = SetWindowAttributes::default();
-        attr.0.background_pixel = BACKGROUND;
+        attr.0.background_pixel = style.background;
         attr.0.border_pixel = 2;
         attr.0.cursor = display.create_font_cursor(34);
-        attr.0.colormap =
-            display.create_colormap(&window, visual.as_raw().visual, xlib::ALLOC_NONE);
+        attr.0.colormap = display.create_colormap(window, visual.as_raw().visual, xlib::ALLOC_NONE);
         attr.0.override_redirect = 1;
         attr.0.border_pixel = 16_000_000;
         attr
@@ -77,22 +108,23 @@ impl<'a> Overlay<'a> {
 
     /// Grabs the keyboard. Prevents the user from launching or interfering
     /// with other applications.
-    fn grab_keyboard(&self) -> Option<()> {
+    fn grab_keyboard(&self) -> Result<(), Error> {
         let ret = self
             .overlay
             .grab_keyboard(true, xlib::GRAB_MODE_SYNC, xlib::GRAB_MODE_ASYNC);
 
         if ret == 0 {
-            return Some(());
+            return Ok(());
         }
-        None
+        Err(Error::GrabFailed("keyboard"))
     }
 
     /// Grabs the pointer - this is necessary to receive motion events while
-    /// any of the mouse buttons are being held down.
-    pub fn grab_pointer(&self, motion: bool) -> Option<()> {
+    /// any of the mouse buttons are being held down, as the select action
+    /// may be bound to any of them.
+    pub fn grab_pointer(&self, motion: bool) -> Result<(), Error> {
         let mask = if motion {
-            xlib::BUTTON1_MOTION_MASK | xlib::BUTTON_RELEASE_MASK | xlib::BUTTON_PRESS_MASK
+            xlib::BUTTON_MOTION_MASK | xlib::BUTTON_RELEASE_MASK | xlib::BUTTON_PRESS_MASK
         } else {
             xlib::BUTTON_PRESS_MASK | xlib::POINTER_MOTION_MASK
         };
@@ -107,6 +139,7 @@ impl<'a> Overlay<'a> {
             0,
             0,
         )
+        .ok_or(Error::GrabFailed("pointer"))
     }
 
     /// Ungrabs the keyboard.
@@ -120,20 +153,165 @@ impl<'a> Overlay<'a> {
         self.display.ungrab_pointer();
     }
 
+    /// Moves the pointer to the given coordinates on the screen.
+    pub fn warp_pointer(&self, x: i32, y: i32) {
+        let rect = Rect {
+            x: 0,
+            y: 0,
+            width: 0,
+            height: 0,
+        };
+        self.display
+            .warp_pointer(None, Some(&self.root), rect, x, y);
+    }
+
+    /// Checks whether the given window is the overlay itself.
+    pub fn is_overlay(&self, window: u64) -> bool {
+        window == self.overlay.as_raw()
+    }
+
     pub fn clear(&mut self) {
         self.overlay.clear();
     }
 
-    /// Draws the rectangle that represents the highlighted region.
+    /// Draws the rectangle that represents the highlighted region, along
+    /// with its outline if a border width is set.
     pub fn draw_rect(&mut self, rect: &Rect) {
         self.display
-            .fill_rectangle(self.overlay.as_raw(), &self.gc, &rect);
+            .fill_rectangle(self.overlay.as_raw(), &self.gc, rect);
+
+        if self.style.border_width > 0 {
+            self.draw_outline(rect, self.style.border_width);
+        }
+    }
+
+    /// Draws an outline of the given width along the inside of the
+    /// rectangle, in the opaque border colour.
+    fn draw_outline(&mut self, rect: &Rect, width: u32) {
+        self.gc.set_foreground(self.style.border);
+        for edge in Self::outline(rect, width) {
+            self.display
+                .fill_rectangle(self.overlay.as_raw(), &self.gc, &edge);
+        }
+        self.gc.set_foreground(self.style.foreground);
+    }
+
+    /// Highlights a window in the picker and labels it with the given
+    /// caption. Shaped windows are filled along their shape; other windows
+    /// are filled and outlined, so that overlapping windows can be told
+    /// apart.
+    pub fn draw_window(&mut self, rect: &Rect, shape: Option<&[Rect]>, caption: &str) {
+        match shape {
+            Some(shape) => {
+                for part in shape {
+                    self.display
+                        .fill_rectangle(self.overlay.as_raw(), &self.gc, part);
+                }
+            }
+            None => {
+                self.display
+                    .fill_rectangle(self.overlay.as_raw(), &self.gc, rect);
+                self.draw_outline(rect, self.style.border_width.max(MIN_OUTLINE_WIDTH));
+            }
+        }
+        self.draw_caption(rect, caption);
+    }
+
+    /// Draws the caption in the top-left corner of the rectangle, in the
+    /// handle colour on the opaque border colour. Captions that are too
+    /// wide for the rectangle are shortened, and omitted if it is too small.
+    fn draw_caption(&mut self, rect: &Rect, caption: &str) {
+        let font = match &self.font {
+            Some(font) => font,
+            None => return,
+        };
+
+        // Core fonts only cover Latin-1.
+        let mut text: Vec<u8> = caption
+            .chars()
+            .map(|c| u8::try_from(u32::from(c)).unwrap_or(b'?'))
+            .collect();
+        let max_width = rect.width as i32 - 2 * CAPTION_PADDING;
+        if font.text_width(&text) > max_width {
+            while !text.is_empty() && font.text_width(&[&text[..], b"..."].concat()) > max_width {
+                text.pop();
+            }
+            text.extend_from_slice(b"...");
+        }
+
+        let width = font.text_width(&text) + 2 * CAPTION_PADDING;
+        let height = font.ascent() + font.descent() + 2 * CAPTION_PADDING;
+        if width > rect.width as i32 || height > rect.height as i32 {
+            return;
+        }
+
+        let background = Rect {
+            x: rect.x,
+            y: rect.y,
+            width: width as u32,
+            height: height as u32,
+        };
+        self.gc.set_foreground(self.style.border);
+        self.display
+            .fill_rectangle(self.overlay.as_raw(), &self.gc, &background);
+        self.gc.set_foreground(self.style.handles);
+        self.display.draw_string(
+            self.overlay.as_raw(),
+            &self.gc,
+            rect.x + CAPTION_PADDING,
+            rect.y + CAPTION_PADDING + font.ascent(),
+            &text,
+        );
+        self.gc.set_foreground(self.style.foreground);
     }
 
-    pub fn show(&self, motion: bool) {
+    /// Returns the edges of an outline of the given width drawn along the
+    /// inside of the rectangle.
+    fn outline(rect: &Rect, width: u32) -> [Rect; 4] {
+        let horizontal = width.min(rect.height);
+        let vertical = width.min(rect.width);
+        let edge = |x, y, width, height| Rect {
+            x,
+            y,
+            width,
+            height,
+        };
+
+        [
+            edge(rect.x, rect.y, rect.width, horizontal),
+            edge(
+                rect.x,
+                rect.y + (rect.height - horizontal) as i32,
+                rect.width,
+                horizontal,
+            ),
+            edge(rect.x, rect.y, vertical, rect.height),
+            edge(
+                rect.x + (rect.width - vertical) as i32,
+                rect.y,
+                vertical,
+                rect.height,
+            ),
+        ]
+    }
+
+    /// Draws the handles used to resize an existing selection. The handles
+    /// are opaque, as the regular foreground would be indistinguishable
+    /// from the highlighted region beneath it.
+    pub fn draw_handles(&mut self, handles: &[Rect]) {
+        self.gc.set_foreground(self.style.handles);
+        for rect in handles {
+            self.display
+                .fill_rectangle(self.overlay.as_raw(), &self.gc, rect);
+        }
+        self.gc.set_foreground(self.style.foreground);
+    }
+
+    pub fn show(&self, motion: bool) -> Result<(), Error> {
+        self.display.set_detectable_autorepeat(true);
         self.display.map_window(&self.overlay);
-        self.grab_keyboard();
-        self.grab_pointer(motion);
+        self.grab_keyboard()?;
+        self.grab_pointer(motion)
     }
 
     pub fn next_event(&mut self) -> Event {
@@ -141,12 +319,10 @@ impl<'a> Overlay<'a> {
             let event = self.display.next_event();
 
             match event.get_kind() {
-                EventKind::Motion(_) => {
-                    if self.time.elapsed().as_nanos() < REFRESH_RATE {
-                        continue;
-                    }
+                EventKind::Motion(_) if self.time.elapsed().as_nanos() < self.frame => {
+                    continue;
                 }
-                EventKind::DestroyWindow(_) => {
+                EventKind::DestroyWindow(e) if self.is_overlay(e.window) => {
                     self.active = false;
                 }
                 _ => (),
@@ -156,6 +332,20 @@ impl<'a> Overlay<'a> {
             return event;
         }
     }
+
+    /// Returns the next queued event about the children of the root, such
+    /// as windows being moved or mapped, without waiting for one.
+    pub fn next_structure_event(&mut self) -> Option<Event> {
+        let event = self
+            .display
+            .check_window_event(&self.root, xlib::SUBSTRUCTURE_NOTIFY_MASK)?;
+        if let EventKind::DestroyWindow(e) = event.get_kind() {
+            if self.is_overlay(e.window) {
+                self.active = false;
+            }
+        }
+        Some(event)
+    }
 }
 
 impl<'a> Drop for Overlay<'a> {
@@ -168,7 +358,7 @@ impl<'a> Drop for Overlay<'a> {
 
             // Sleep for the duration of a single frame to make sure
             // the buffer is new.
-            let frame = Duration::from_nanos(REFRESH_RATE as u64 + 1);
+            let frame = Duration::from_nanos(self.frame as u64 + 1);
             std::thread::sleep(frame);
         }
     }
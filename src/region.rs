@@ -1,18 +1,214 @@
+use crate::clients;
 use crate::errors::Error;
-use crate::overlay::Overlay;
+use crate::keymap::{self, Action, Keymap};
+use crate::overlay::{Overlay, Style};
+use std::time::{Duration, Instant};
 use xlib::{Display, EventKind, Rect};
 
-const MOUSE_LEFT: u32 = 1;
-const MOUSE_RIGHT: u32 = 3;
+const HANDLE_SIZE: i32 = 8;
+const SNAP_DISTANCE: i32 = 8;
+
+// Arrow keys pressed within this interval of each other accelerate the
+// keyboard-controlled pointer.
+const ACCELERATION_DELAY: Duration = Duration::from_millis(150);
+
+// Window snapping would swallow single-pixel steps, so it is disabled while
+// the selection is made with the keyboard.
+const KEYBOARD_STATE: u32 = xlib::CONTROL_MASK;
+
+/// The part of a selection that is being dragged while adjusting it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Handle {
+    TopLeft,
+    Top,
+    TopRight,
+    Right,
+    BottomRight,
+    Bottom,
+    BottomLeft,
+    Left,
+    Body,
+}
 
 pub struct Region<'a> {
     overlay: Overlay<'a>,
+    keymap: &'a Keymap,
+    adjust: bool,
+    aspect: (u32, u32),
+    size: Option<(u32, u32)>,
+    min_size: (u32, u32),
+    max_size: (u32, u32),
+    snap: u32,
+    windows: Vec<Rect>,
 }
 
 impl<'a> Region<'a> {
-    pub fn new(display: &'a Display) -> Self {
-        let overlay = Overlay::new(display);
-        Self { overlay }
+    pub fn new(display: &'a Display, keymap: &'a Keymap, style: Style) -> Result<Self, Error> {
+        let overlay = Overlay::new(display, style)?;
+        let windows = clients::get_all_windows(display)
+            .iter()
+            .map(|w| clients::screen_rect(display, w))
+            .collect();
+
+        Ok(Self {
+            overlay,
+            keymap,
+            adjust: false,
+            aspect: (1, 1),
+            size: None,
+            min_size: (0, 0),
+            max_size: (u32::MAX, u32::MAX),
+            snap: 1,
+            windows,
+        })
+    }
+
+    /// Keeps the selection open for adjustments after the mouse button is
+    /// released, rather than returning it immediately.
+    pub fn adjustable(mut self, adjust: bool) -> Self {
+        self.adjust = adjust;
+        self
+    }
+
+    /// Sets the aspect ratio the selection is locked to while Shift is held.
+    pub fn aspect(mut self, aspect: Option<(u32, u32)>) -> Self {
+        self.aspect = aspect.unwrap_or((1, 1));
+        self
+    }
+
+    /// Replaces the drag with a box of a fixed size that follows the pointer.
+    pub fn size(mut self, size: Option<(u32, u32)>) -> Self {
+        self.size = size;
+        self
+    }
+
+    /// Sets the smallest selection that can be made.
+    pub fn min_size(mut self, size: Option<(u32, u32)>) -> Self {
+        self.min_size = size.unwrap_or((0, 0));
+        self
+    }
+
+    /// Sets the largest selection that can be made.
+    pub fn max_size(mut self, size: Option<(u32, u32)>) -> Self {
+        self.max_size = size.unwrap_or((u32::MAX, u32::MAX));
+        self
+    }
+
+    /// Rounds the origin and size of the selection to multiples of `snap`.
+    pub fn snap(mut self, snap: Option<u32>) -> Self {
+        self.snap = snap.unwrap_or(1).max(1);
+        self
+    }
+
+    /// Turns the start and end points of a drag into a `Rect`, taking the
+    /// held modifiers into account. Shift locks the aspect ratio, and Alt
+    /// grows the selection symmetrically around the start point.
+    fn shape(&self, start: (i32, i32), end: (i32, i32), state: u32) -> Rect {
+        let end = self.snap_point(end, state);
+        let (mut dx, mut dy) = (end.0 - start.0, end.1 - start.1);
+
+        if state & xlib::SHIFT_MASK != 0 {
+            let (ratio_w, ratio_h) = (self.aspect.0 as i64, self.aspect.1 as i64);
+            let width = i64::max(dx.abs() as i64, dy.abs() as i64 * ratio_w / ratio_h);
+            let height = width * ratio_h / ratio_w;
+            dx = if dx < 0 { -width } else { width } as i32;
+            dy = if dy < 0 { -height } else { height } as i32;
+        }
+
+        let centered = state & xlib::MOD1_MASK != 0;
+        let (min, max, snap) = (self.min_size, self.max_size, self.snap);
+        let (x, width) = Self::span(start.0, dx, centered, min.0, max.0, snap);
+        let (y, height) = Self::span(start.1, dy, centered, min.1, max.1, snap);
+        self.clip(&Rect {
+            x,
+            y,
+            width,
+            height,
+        })
+    }
+
+    /// Computes the position and length of one axis of the selection, which
+    /// grows from `anchor` by `delta` pixels, or by `delta` pixels in both
+    /// directions if `centered`. The size constraints and snapping are
+    /// applied to the result, rounding to multiples of `snap`.
+    fn span(anchor: i32, delta: i32, centered: bool, min: u32, max: u32, snap: u32) -> (i32, u32) {
+        let factor = if centered { 2 } else { 1 };
+        let length = (delta.unsigned_abs() * factor).max(min).min(max);
+        let length = Self::round(length as i32, snap) as u32;
+
+        let start = if centered {
+            anchor - length as i32 / 2
+        } else if delta < 0 {
+            anchor - length as i32
+        } else {
+            anchor
+        };
+        (Self::round(start, snap), length)
+    }
+
+    /// Snaps the given point to the nearest window edges within
+    /// `SNAP_DISTANCE` pixels. Snapping is disabled while Control is held.
+    fn snap_point(&self, point: (i32, i32), state: u32) -> (i32, i32) {
+        (
+            self.snap_x(point.0, point.1, state),
+            self.snap_y(point.1, point.0, state),
+        )
+    }
+
+    /// Snaps a vertical edge at `x` to the left or right edge of a window
+    /// that spans `y`.
+    fn snap_x(&self, x: i32, y: i32, state: u32) -> i32 {
+        if state & xlib::CONTROL_MASK != 0 {
+            return x;
+        }
+
+        let edges = self
+            .windows
+            .iter()
+            .filter(|w| y >= w.y && y <= w.y + w.height as i32)
+            .flat_map(|w| [w.x, w.x + w.width as i32]);
+        Self::nearest(x, edges)
+    }
+
+    /// Snaps a horizontal edge at `y` to the top or bottom edge of a window
+    /// that spans `x`.
+    fn snap_y(&self, y: i32, x: i32, state: u32) -> i32 {
+        if state & xlib::CONTROL_MASK != 0 {
+            return y;
+        }
+
+        let edges = self
+            .windows
+            .iter()
+            .filter(|w| x >= w.x && x <= w.x + w.width as i32)
+            .flat_map(|w| [w.y, w.y + w.height as i32]);
+        Self::nearest(y, edges)
+    }
+
+    /// Returns the edge closest to `value`, or `value` itself if none of the
+    /// edges are within `SNAP_DISTANCE` pixels.
+    fn nearest<I: Iterator<Item = i32>>(value: i32, edges: I) -> i32 {
+        edges
+            .filter(|edge| (edge - value).abs() <= SNAP_DISTANCE)
+            .min_by_key(|edge| (edge - value).abs())
+            .unwrap_or(value)
+    }
+
+    /// Rounds the given value to the nearest multiple of `snap`.
+    fn round(value: i32, snap: u32) -> i32 {
+        let snap = snap as i32;
+        (value + snap / 2).div_euclid(snap) * snap
+    }
+
+    /// Returns the part of `rect` that lies on the screen.
+    fn clip(&self, rect: &Rect) -> Rect {
+        let bounds = &self.overlay.bounds;
+        let max_x = bounds.x + bounds.width as i32;
+        let max_y = bounds.y + bounds.height as i32;
+        let (left, top) = (rect.x.max(bounds.x), rect.y.max(bounds.y));
+        let right = (rect.x + rect.width as i32).min(max_x).max(left);
+        let bottom = (rect.y + rect.height as i32).min(max_y).max(top);
+        Self::to_rect((left, top), (right, bottom))
     }
 
     /// Helper function for turning tuples into `Rect` structures.
@@ -21,54 +217,469 @@ impl<'a> Region<'a> {
         Rect {
             x: i32::min(start.0, end.0),
             y: i32::min(start.1, end.1),
-            width: (end.0 - start.0).abs() as u32,
-            height: (end.1 - start.1).abs() as u32,
+            width: (end.0 - start.0).unsigned_abs(),
+            height: (end.1 - start.1).unsigned_abs(),
+        }
+    }
+
+    /// Returns the handle of `rect` found at the given coordinates, if any.
+    /// Corners take precedence over edges, which in turn take precedence
+    /// over the body of the selection.
+    fn handle_at(rect: &Rect, x: i32, y: i32) -> Option<Handle> {
+        let (left, top) = (rect.x, rect.y);
+        let (right, bottom) = (left + rect.width as i32, top + rect.height as i32);
+        let near = |a: i32, b: i32| (a - b).abs() <= HANDLE_SIZE;
+
+        if x < left - HANDLE_SIZE
+            || x > right + HANDLE_SIZE
+            || y < top - HANDLE_SIZE
+            || y > bottom + HANDLE_SIZE
+        {
+            return None;
+        }
+
+        let handle = match (near(x, left), near(x, right), near(y, top), near(y, bottom)) {
+            (true, _, true, _) => Handle::TopLeft,
+            (_, true, true, _) => Handle::TopRight,
+            (true, _, _, true) => Handle::BottomLeft,
+            (_, true, _, true) => Handle::BottomRight,
+            (true, _, _, _) => Handle::Left,
+            (_, true, _, _) => Handle::Right,
+            (_, _, true, _) => Handle::Top,
+            (_, _, _, true) => Handle::Bottom,
+            _ if x > left && x < right && y > top && y < bottom => Handle::Body,
+            _ => return None,
+        };
+        Some(handle)
+    }
+
+    /// Returns the rectangles of the handles drawn on top of the selection.
+    fn handles(rect: &Rect) -> Vec<Rect> {
+        let (x, y) = (rect.x, rect.y);
+        let (width, height) = (rect.width as i32, rect.height as i32);
+        let half = HANDLE_SIZE / 2;
+
+        [
+            (x, y),
+            (x + width / 2, y),
+            (x + width, y),
+            (x + width, y + height / 2),
+            (x + width, y + height),
+            (x + width / 2, y + height),
+            (x, y + height),
+            (x, y + height / 2),
+        ]
+        .iter()
+        .map(|&(x, y)| Rect {
+            x: x - half,
+            y: y - half,
+            width: HANDLE_SIZE as u32,
+            height: HANDLE_SIZE as u32,
+        })
+        .collect()
+    }
+
+    /// Moves the edges belonging to `handle` by the given offset. Dragging an
+    /// edge past its opposite flips the selection rather than collapsing it.
+    /// Moving the body only snaps its origin, keeping the size intact, while
+    /// dragged edges also snap to nearby windows along the pointer.
+    fn drag(
+        &self,
+        handle: Handle,
+        rect: &Rect,
+        dx: i32,
+        dy: i32,
+        pointer: (i32, i32),
+        state: u32,
+    ) -> Rect {
+        use Handle::*;
+
+        let snap = self.snap;
+        if handle == Body {
+            let moved = self.translate(rect, dx, dy);
+            let (x, y) = (Self::round(moved.x, snap), Self::round(moved.y, snap));
+            return self.translate(&moved, x - moved.x, y - moved.y);
+        }
+
+        let (left, top) = (rect.x, rect.y);
+        let (right, bottom) = (left + rect.width as i32, top + rect.height as i32);
+        let (min, max) = (self.min_size, self.max_size);
+        let snap_x = |x| self.snap_x(x, pointer.1, state);
+        let snap_y = |y| self.snap_y(y, pointer.0, state);
+
+        let (x, width) = match handle {
+            TopLeft | Left | BottomLeft => {
+                Self::span(right, snap_x(left + dx) - right, false, min.0, max.0, snap)
+            }
+            TopRight | Right | BottomRight => {
+                Self::span(left, snap_x(right + dx) - left, false, min.0, max.0, snap)
+            }
+            _ => (rect.x, rect.width),
+        };
+        let (y, height) = match handle {
+            TopLeft | Top | TopRight => {
+                Self::span(bottom, snap_y(top + dy) - bottom, false, min.1, max.1, snap)
+            }
+            BottomLeft | Bottom | BottomRight => {
+                Self::span(top, snap_y(bottom + dy) - top, false, min.1, max.1, snap)
+            }
+            _ => (rect.y, rect.height),
+        };
+
+        self.clip(&Rect {
+            x,
+            y,
+            width,
+            height,
+        })
+    }
+
+    /// Moves the whole selection by the given offset, without letting it
+    /// leave the screen.
+    fn translate(&self, rect: &Rect, dx: i32, dy: i32) -> Rect {
+        let bounds = &self.overlay.bounds;
+        let max_x = bounds.x + bounds.width as i32 - rect.width as i32;
+        let max_y = bounds.y + bounds.height as i32 - rect.height as i32;
+
+        Rect {
+            x: (rect.x + dx).min(max_x).max(bounds.x),
+            y: (rect.y + dy).min(max_y).max(bounds.y),
+            ..*rect
+        }
+    }
+
+    /// Re-draws the selection, along with its handles when adjusting.
+    fn redraw(&mut self, rect: &Rect, handles: bool) {
+        self.overlay.clear();
+        self.overlay.draw_rect(rect);
+        if handles {
+            self.overlay.draw_handles(&Self::handles(rect));
         }
     }
 
     pub fn show(&mut self) -> Result<Rect, Error> {
-        self.overlay.show(true);
+        let rect = match self.size {
+            Some(size) => {
+                // A box larger than the screen cannot be captured at its
+                // exact size.
+                let bounds = self.overlay.bounds;
+                if size.0 > bounds.width || size.1 > bounds.height {
+                    return Err(Error::OutOfBounds(Rect {
+                        width: size.0,
+                        height: size.1,
+                        ..bounds
+                    }));
+                }
+
+                self.overlay.show(false)?;
+                let rect = self.place(size)?;
+
+                // Motion events are only needed while dragging from here on.
+                self.overlay.grab_pointer(true)?;
+                rect
+            }
+            None => {
+                self.overlay.show(true)?;
+                self.select()?
+            }
+        };
+
+        if self.adjust {
+            return self.adjust_selection(rect);
+        }
+        Ok(rect)
+    }
+
+    /// Centres a box of the given size on the given coordinates, keeping it
+    /// on the screen.
+    fn centre(&self, size: (u32, u32), x: i32, y: i32) -> Rect {
+        let rect = Rect {
+            x: Self::round(x - size.0 as i32 / 2, self.snap),
+            y: Self::round(y - size.1 as i32 / 2, self.snap),
+            width: size.0,
+            height: size.1,
+        };
+        self.clip(&self.translate(&rect, 0, 0))
+    }
+
+    /// Lets the user place a box of a fixed size, which follows the pointer
+    /// until either mouse button is clicked.
+    fn place(&mut self, size: (u32, u32)) -> Result<Rect, Error> {
+        let pointer = self.overlay.display.query_pointer(&self.overlay.root);
+        let mut rect = self.centre(size, pointer.x, pointer.y);
+        self.redraw(&rect, false);
+
+        loop {
+            let event = self.overlay.next_event();
+
+            match event.get_kind() {
+                // A mouse button that we monitor was pressed.
+                EventKind::ButtonPress(event) => match self.keymap.button(event.button) {
+                    Some(Action::Select) => return Ok(rect),
+                    Some(Action::Cancel) => break,
+                    _ => (),
+                },
+
+                // A key event that we monitor was triggered.
+                EventKind::KeyPress(event) => match self.keymap.key(event) {
+                    Some(Action::Cancel) => break,
+                    Some(Action::Confirm) => return Ok(rect),
+                    _ => (),
+                },
+
+                // The pointer moved; move the box along with it.
+                EventKind::Motion(event) => {
+                    rect = self.centre(size, event.x_root, event.y_root);
+                    self.redraw(&rect, false);
+                }
+
+                // The window was destroyed by external means.
+                EventKind::DestroyWindow(_) => {
+                    return Err(Error::WindowDestroyed);
+                }
+                _ => (),
+            }
+        }
+
+        Err(Error::Cancelled)
+    }
+
+    /// Keeps the given point within the screen.
+    fn clamp_point(&self, point: (i32, i32)) -> (i32, i32) {
+        let bounds = &self.overlay.bounds;
+        let max_x = bounds.x + bounds.width as i32 - 1;
+        let max_y = bounds.y + bounds.height as i32 - 1;
+        (
+            point.0.min(max_x).max(bounds.x),
+            point.1.min(max_y).max(bounds.y),
+        )
+    }
+
+    /// Lets the user drag out the initial selection. The held modifiers are
+    /// tracked throughout the drag, and holding the anchor key (Space by
+    /// default) moves the selection instead of resizing it.
+    ///
+    /// The selection can also be made with the keyboard alone: the arrow keys
+    /// move the pointer, the anchor key anchors the selection, after which
+    /// the arrow keys extend it, and confirming captures it.
+    fn select(&mut self) -> Result<Rect, Error> {
         let mut start = (0, 0);
+        let mut end = (0, 0);
+        let mut dragging = false;
+        let mut moving = false;
+        let mut anchor = None;
+        let mut last_step = Instant::now();
+        let mut repeats = 0;
 
         loop {
             let event = self.overlay.next_event();
 
             match event.get_kind() {
-                // Either the primary or secondary mouse button was pressed
-                EventKind::ButtonPress(event) => match event.button {
-                    MOUSE_LEFT => start = (event.x_root, event.y_root),
-                    MOUSE_RIGHT => break,
+                // A mouse button that we monitor was pressed.
+                EventKind::ButtonPress(event) => match self.keymap.button(event.button) {
+                    Some(Action::Select) => {
+                        start = self.snap_point((event.x_root, event.y_root), event.state);
+                        end = start;
+                        dragging = true;
+                        anchor = None;
+                    }
+                    Some(Action::Cancel) => break,
                     _ => (),
                 },
 
-                // The left mouse button was released; check the coordinates
+                // The select button was released; check the coordinates
                 // and return a `Rect` structure containing them.
-                EventKind::ButtonRelease(event) => {
-                    if event.button == MOUSE_LEFT {
-                        let rect = Self::to_rect(start, (event.x_root, event.y_root));
+                EventKind::ButtonRelease(event)
+                    if self.keymap.button(event.button) == Some(Action::Select) =>
+                {
+                    let rect = self.shape(start, (event.x_root, event.y_root), event.state);
 
-                        if rect.width == 0 || rect.height == 0 {
-                            return Err(Error::InvalidRect);
-                        }
-                        return Ok(rect);
+                    if rect.width == 0 || rect.height == 0 {
+                        return Err(Error::InvalidRect);
                     }
+                    return Ok(rect);
                 }
 
-                // A key event that we monitor was triggered.
-                EventKind::KeyPress(event) => match event.keycode {
-                    // escape, q
-                    9 | 24 => break,
+                // A key event that we monitor was triggered. The state of a
+                // key event does not include the key itself, so modifiers
+                // are added and removed manually.
+                EventKind::KeyPress(event) => match self.keymap.key(event) {
+                    Some(Action::Cancel) => break,
+
+                    Some(Action::Confirm) => {
+                        if let Some(anchor) = anchor {
+                            let cursor = (event.x_root, event.y_root);
+                            let rect = self.shape(anchor, cursor, KEYBOARD_STATE);
+
+                            if rect.width == 0 || rect.height == 0 {
+                                return Err(Error::InvalidRect);
+                            }
+                            return Ok(rect);
+                        }
+                    }
+
+                    Some(Action::Anchor) if dragging => moving = true,
+                    Some(Action::Anchor) => anchor = Some((event.x_root, event.y_root)),
+
+                    // Move the pointer, accelerating while the key is held,
+                    // and extend the selection once anchored.
+                    Some(action) if !dragging && action.direction().is_some() => {
+                        let (dx, dy) = action.direction().unwrap_or((0, 0));
+                        repeats = if last_step.elapsed() < ACCELERATION_DELAY {
+                            repeats + 1
+                        } else {
+                            0
+                        };
+                        last_step = Instant::now();
+
+                        let mut step = 1 << (repeats / 8).min(5);
+                        if event.state & xlib::SHIFT_MASK != 0 {
+                            step *= 10;
+                        }
+
+                        let cursor = (event.x_root + dx * step, event.y_root + dy * step);
+                        let cursor = self.clamp_point(cursor);
+                        self.overlay.warp_pointer(cursor.0, cursor.1);
+
+                        if let Some(anchor) = anchor {
+                            self.redraw(&self.shape(anchor, cursor, KEYBOARD_STATE), false);
+                        }
+                    }
+
+                    // shift, alt, control
+                    None if keymap::modifier(event) != 0 => {
+                        let state = event.state | keymap::modifier(event);
+                        if dragging {
+                            self.redraw(&self.shape(start, end, state), false);
+                        }
+                    }
 
                     // ignore the rest
                     _ => (),
                 },
 
+                EventKind::KeyRelease(event) => match self.keymap.key(event) {
+                    Some(Action::Anchor) => moving = false,
+
+                    // shift, alt, control
+                    None if keymap::modifier(event) != 0 => {
+                        let state = event.state & !keymap::modifier(event);
+                        if dragging {
+                            self.redraw(&self.shape(start, end, state), false);
+                        }
+                    }
+
+                    // ignore the rest
+                    _ => (),
+                },
+
+                // The mouse moved while the select button was being held.
+                // Re-draw the rectangle and update the end position, moving
+                // the start position along with it while Space is held.
+                EventKind::Motion(event) if dragging => {
+                    let pointer = (event.x_root, event.y_root);
+                    if moving {
+                        start.0 += pointer.0 - end.0;
+                        start.1 += pointer.1 - end.1;
+                    }
+
+                    end = pointer;
+                    self.redraw(&self.shape(start, end, event.state), false);
+                }
+
+                // The window was destroyed by external means.
+                EventKind::DestroyWindow(_) => {
+                    return Err(Error::WindowDestroyed);
+                }
+                _ => (),
+            }
+        }
+
+        Err(Error::Cancelled)
+    }
+
+    /// Lets the user resize and move a finished selection until it is either
+    /// confirmed or cancelled.
+    fn adjust_selection(&mut self, mut rect: Rect) -> Result<Rect, Error> {
+        // The grabbed handle, the pointer position when it was grabbed and
+        // the rectangle the drag is applied to.
+        let mut grab: Option<(Handle, (i32, i32), Rect)> = None;
+        let mut previous = rect;
+        self.redraw(&rect, true);
+
+        loop {
+            let event = self.overlay.next_event();
+
+            match event.get_kind() {
+                // Grab the handle under the cursor. Pressing outside of the
+                // selection starts a new one from that point.
+                EventKind::ButtonPress(event) => match self.keymap.button(event.button) {
+                    Some(Action::Select) => {
+                        let (x, y) = (event.x_root, event.y_root);
+                        previous = rect;
+                        grab = match Self::handle_at(&rect, x, y) {
+                            Some(handle) => Some((handle, (x, y), rect)),
+                            None => Some((
+                                Handle::BottomRight,
+                                (x, y),
+                                Rect {
+                                    x: self.snap_x(x, y, event.state),
+                                    y: self.snap_y(y, x, event.state),
+                                    width: 0,
+                                    height: 0,
+                                },
+                            )),
+                        };
+                    }
+                    Some(Action::Cancel) => break,
+                    _ => (),
+                },
+
+                // Let go of the handle. A selection that was collapsed to
+                // nothing is reverted to what it was before the drag.
+                EventKind::ButtonRelease(event)
+                    if self.keymap.button(event.button) == Some(Action::Select) =>
+                {
+                    let released = grab.take().is_some();
+                    if released && (rect.width == 0 || rect.height == 0) {
+                        rect = previous;
+                        self.redraw(&rect, true);
+                    }
+                }
+
+                // A key event that we monitor was triggered. Arrow keys nudge
+                // the selection by a single pixel, or by ten with Shift held.
+                EventKind::KeyPress(event) => {
+                    let step = if event.state & xlib::SHIFT_MASK != 0 {
+                        10
+                    } else {
+                        1
+                    };
+
+                    let (dx, dy) = match self.keymap.key(event) {
+                        Some(Action::Cancel) => break,
+                        Some(Action::Confirm) => return Ok(rect),
+
+                        // arrow keys; ignore the rest
+                        action => match action.and_then(Action::direction) {
+                            Some(direction) => direction,
+                            None => continue,
+                        },
+                    };
+
+                    rect = self.translate(&rect, dx * step, dy * step);
+                    self.redraw(&rect, true);
+                }
+
                 // The mouse moved while the primary button was being held.
-                // Re-draw the rectangle and update the end position.
+                // Apply the movement to the grabbed handle.
                 EventKind::Motion(event) => {
-                    let rect = Self::to_rect(start, (event.x_root, event.y_root));
-                    self.overlay.clear();
-                    self.overlay.draw_rect(&rect);
+                    if let Some((handle, (x, y), original)) = grab {
+                        let (dx, dy) = (event.x_root - x, event.y_root - y);
+                        let pointer = (event.x_root, event.y_root);
+                        rect = self.drag(handle, &original, dx, dy, pointer, event.state);
+                        self.redraw(&rect, true);
+                    }
                 }
 
                 // The window was destroyed by external means.
@@ -82,3 +693,95 @@ impl<'a> Region<'a> {
         Err(Error::Cancelled)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RECT: Rect = Rect {
+        x: 100,
+        y: 100,
+        width: 200,
+        height: 100,
+    };
+
+    #[test]
+    fn finds_corners_and_edges() {
+        let at = |x, y| Region::handle_at(&RECT, x, y);
+        assert_eq!(at(100, 100), Some(Handle::TopLeft));
+        assert_eq!(at(305, 95), Some(Handle::TopRight));
+        assert_eq!(at(92, 208), Some(Handle::BottomLeft));
+        assert_eq!(at(300, 200), Some(Handle::BottomRight));
+        assert_eq!(at(200, 100), Some(Handle::Top));
+        assert_eq!(at(200, 203), Some(Handle::Bottom));
+        assert_eq!(at(96, 150), Some(Handle::Left));
+        assert_eq!(at(304, 150), Some(Handle::Right));
+        assert_eq!(at(200, 150), Some(Handle::Body));
+    }
+
+    #[test]
+    fn ignores_points_outside_the_selection() {
+        assert_eq!(Region::handle_at(&RECT, 91, 150), None);
+        assert_eq!(Region::handle_at(&RECT, 200, 209), None);
+        assert_eq!(Region::handle_at(&RECT, 0, 0), None);
+    }
+
+    #[test]
+    fn prefers_corners_on_small_selections() {
+        let small = Rect {
+            x: 100,
+            y: 100,
+            width: 4,
+            height: 4,
+        };
+        assert_eq!(Region::handle_at(&small, 102, 102), Some(Handle::TopLeft));
+    }
+
+    #[test]
+    fn centres_handles_on_the_outline() {
+        let handles = Region::handles(&RECT);
+        assert_eq!(handles.len(), 8);
+        for handle in &handles {
+            let (x, y) = (handle.x + HANDLE_SIZE / 2, handle.y + HANDLE_SIZE / 2);
+            assert!(Region::handle_at(&RECT, x, y).is_some_and(|h| h != Handle::Body));
+        }
+    }
+
+    #[test]
+    fn rounds_to_the_snapping_size() {
+        assert_eq!(Region::round(13, 1), 13);
+        assert_eq!(Region::round(13, 10), 10);
+        assert_eq!(Region::round(15, 10), 20);
+        assert_eq!(Region::round(-4, 10), 0);
+        assert_eq!(Region::round(-6, 10), -10);
+    }
+
+    #[test]
+    fn spans_from_the_anchor() {
+        assert_eq!(Region::span(100, 50, false, 1, 1000, 1), (100, 50));
+        assert_eq!(Region::span(100, -50, false, 1, 1000, 1), (50, 50));
+        assert_eq!(Region::span(100, 50, true, 1, 1000, 1), (50, 100));
+    }
+
+    #[test]
+    fn constrains_the_size() {
+        assert_eq!(Region::span(100, 5, false, 20, 1000, 1), (100, 20));
+        assert_eq!(Region::span(100, -5, false, 20, 1000, 1), (80, 20));
+        assert_eq!(Region::span(100, 500, false, 1, 200, 1), (100, 200));
+        assert_eq!(Region::span(100, 500, true, 1, 200, 1), (0, 200));
+    }
+
+    #[test]
+    fn snaps_origin_and_size() {
+        assert_eq!(Region::span(103, 47, false, 1, 1000, 10), (100, 50));
+        assert_eq!(Region::span(103, -44, false, 1, 1000, 10), (60, 40));
+    }
+
+    #[test]
+    fn snaps_to_nearby_edges() {
+        let edges = || vec![0, 100, 200].into_iter();
+        assert_eq!(Region::nearest(95, edges()), 100);
+        assert_eq!(Region::nearest(104, edges()), 100);
+        assert_eq!(Region::nearest(150, edges()), 150);
+    }
+}
@@ -1,20 +1,98 @@
 extern crate image;
 extern crate xlib;
 
+use crate::capture::Capture;
+use crate::clients;
+use crate::color;
 use crate::errors::Error;
-use image::{ImageBuffer, ImageFormat, RgbImage};
-use std::io;
-use std::path::Path;
+use crate::metadata;
+use image::{DynamicImage, ImageBuffer, ImageFormat, RgbImage, RgbaImage};
+use png::HasParameters;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
 use xlib::{Display, Image, Rect, Window};
 
 const RED_MASK: u64 = 16_711_680;
 const GREEN_MASK: u64 = 65_280;
 const BLUE_MASK: u64 = 255;
 
+/// The image formats screenshots can be saved as.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    Png,
+    Jpeg,
+    Bmp,
+}
+
+impl Format {
+    pub const NAMES: [&'static str; 3] = ["png", "jpeg", "bmp"];
+
+    /// Parses a format name, also accepting common file extensions.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "png" => Some(Format::Png),
+            "jpeg" | "jpg" => Some(Format::Jpeg),
+            "bmp" => Some(Format::Bmp),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Png => "png",
+            Format::Jpeg => "jpg",
+            Format::Bmp => "bmp",
+        }
+    }
+
+    fn image_format(self) -> ImageFormat {
+        match self {
+            Format::Png => ImageFormat::PNG,
+            Format::Jpeg => ImageFormat::JPEG,
+            Format::Bmp => ImageFormat::BMP,
+        }
+    }
+}
+
+/// What to do when a screenshot is saved to a path that already exists.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OnExists {
+    /// Appends the lowest free number to the name, e.g. `name-2.png`.
+    Increment,
+    Overwrite,
+    Fail,
+}
+
+impl OnExists {
+    pub const NAMES: [&'static str; 3] = ["increment", "overwrite", "fail"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "increment" => Some(OnExists::Increment),
+            "overwrite" => Some(OnExists::Overwrite),
+            "fail" => Some(OnExists::Fail),
+            _ => None,
+        }
+    }
+}
+
 pub struct Screenshot {
     data: Image,
     width: u32,
     height: u32,
+    /// ICC profile of the display the screenshot was taken on.
+    profile: Option<Vec<u8>>,
+    /// Whether the colours are converted from `profile` to sRGB, rather
+    /// than embedding it.
+    srgb: bool,
+    /// The bounding shape of the captured window, if it is shaped. Pixels
+    /// outside of it are transparent in formats that support it.
+    shape: Option<Vec<Rect>>,
 }
 
 impl Screenshot {
@@ -23,7 +101,7 @@ impl Screenshot {
         let width = display.get_width(0) as u32;
         let height = display.get_height(0) as u32;
         Self::with_rect(
-            &display,
+            display,
             &root,
             Rect {
                 x: 0,
@@ -36,23 +114,67 @@ impl Screenshot {
 
     pub fn window(display: &Display, window: &Window) -> Result<Self, Error> {
         let rect = window.get_rect();
-        Self::with_rect(
+        let rect = Rect {
+            x: 0,
+            y: 0,
+            width: rect.width,
+            height: rect.height,
+        };
+
+        let mut screenshot = match Self::composited(display, window, rect) {
+            Some(screenshot) => screenshot,
+            None => Self::with_rect(display, window, rect)?,
+        };
+        screenshot.shape = display.get_shape(window);
+        Ok(screenshot)
+    }
+
+    /// Captures a window from the pixmap the compositor renders its
+    /// top-level window into. Unlike reading the screen, this captures the
+    /// contents of windows that are covered by others. Returns `None` if
+    /// the top-level window is not redirected by a compositor.
+    fn composited(display: &Display, window: &Window, rect: Rect) -> Option<Self> {
+        let top_level = clients::top_level(display, window);
+        let border = top_level.get_attributes()?.border_width as i32;
+        let pixmap = display.name_window_pixmap(&top_level)?;
+
+        // The pixmap starts at the outer edge of the border.
+        let (x, y) = display.translate_coordinates(window, 0, 0);
+        let (top_x, top_y) = display.translate_coordinates(&top_level, 0, 0);
+        let data = Image::get_image(
             display,
-            window,
-            Rect {
-                x: 0,
-                y: 0,
-                width: rect.width,
-                height: rect.height,
-            },
-        )
+            pixmap.as_raw(),
+            x - top_x + border,
+            y - top_y + border,
+            rect.width,
+            rect.height,
+            xlib::Z_PIXMAP,
+        )?;
+
+        Some(Self {
+            data,
+            width: rect.width,
+            height: rect.height,
+            profile: None,
+            srgb: false,
+            shape: None,
+        })
     }
 
     pub fn with_rect(display: &Display, window: &Window, rect: Rect) -> Result<Self, Error> {
+        let bounds = window.get_rect();
+        if rect.x < 0
+            || rect.y < 0
+            || rect.x as u64 + u64::from(rect.width) > u64::from(bounds.width)
+            || rect.y as u64 + u64::from(rect.height) > u64::from(bounds.height)
+        {
+            return Err(Error::OutOfBounds(rect));
+        }
+
         Ok(Self {
             data: Image::get_image(
-                &display,
-                &window,
+                display,
+                window.as_raw(),
                 rect.x,
                 rect.y,
                 rect.width,
@@ -62,9 +184,20 @@ impl Screenshot {
             .ok_or(Error::ImageError)?,
             width: rect.width,
             height: rect.height,
+            profile: None,
+            srgb: false,
+            shape: None,
         })
     }
 
+    /// Sets the ICC profile of the display, which is either embedded into
+    /// the saved image or used to convert its colours to sRGB.
+    pub fn profile(mut self, profile: Option<Vec<u8>>, srgb: bool) -> Self {
+        self.profile = profile;
+        self.srgb = srgb;
+        self
+    }
+
     #[inline]
     const fn get_colors(pixel: u64) -> [u8; 3] {
         let red = (pixel & RED_MASK) >> 16;
@@ -73,13 +206,344 @@ impl Screenshot {
         [red as u8, green as u8, blue as u8]
     }
 
-    pub fn save<P: AsRef<Path>>(self, path: P) -> io::Result<()> {
-        let image: RgbImage = ImageBuffer::from_fn(self.width, self.height, |x, y| {
+    /// Saves the screenshot, returning the path it was saved at. The image
+    /// is written to a temporary file in the same directory first, and only
+    /// moved into place once it has been written completely.
+    pub fn save(
+        self,
+        path: &Path,
+        format: Format,
+        on_exists: OnExists,
+        capture: &Capture,
+    ) -> Result<PathBuf, Error> {
+        let name = path
+            .file_name()
+            .ok_or_else(|| Error::InvalidPath(path.to_string_lossy().into_owned()))?;
+        let temp = path.with_file_name(format!(
+            ".{}.{}.tmp",
+            name.to_string_lossy(),
+            process::id()
+        ));
+
+        let result = self
+            .write(&temp, path, format, capture)
+            .and_then(|_| Self::persist(&temp, path, on_exists));
+
+        if result.is_err() {
+            let _ = fs::remove_file(&temp);
+        }
+        result
+    }
+
+    /// Encodes the screenshot into a new file at `temp`, along with metadata
+    /// describing the capture. Errors are reported for `path`, as the
+    /// temporary file is an implementation detail.
+    fn write(self, temp: &Path, path: &Path, format: Format, capture: &Capture) -> Result<(), Error> {
+        let mut image: RgbImage = ImageBuffer::from_fn(self.width, self.height, |x, y| {
             let pixel = self.data.get_pixel(x as i32, y as i32);
             let colors = Self::get_colors(pixel);
             image::Rgb(colors)
         });
 
-        image.save_with_format(path, ImageFormat::PNG)
+        let profile = match &self.profile {
+            Some(profile) if self.srgb => {
+                color::to_srgb(profile, &mut image);
+                None
+            }
+            profile => profile.as_deref(),
+        };
+
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(temp)
+            .map_err(|e| Error::write(e, path))?;
+        let mut writer = BufWriter::new(file);
+
+        match format {
+            Format::Png => {
+                let image = match &self.shape {
+                    Some(shape) => DynamicImage::ImageRgba8(Self::apply_shape(image, shape)),
+                    None => DynamicImage::ImageRgb8(image),
+                };
+                Self::encode_png(&mut writer, image, capture, profile, self.srgb).map_err(|e| {
+                    match e {
+                        png::EncodingError::IoError(e) => Error::write(e, path),
+                        e => Error::EncodingError(Box::new(e)),
+                    }
+                })?
+            }
+            Format::Jpeg => {
+                let mut jpeg = Vec::new();
+                DynamicImage::ImageRgb8(image)
+                    .write_to(&mut jpeg, format.image_format())
+                    .map_err(|e| Error::EncodingError(Box::new(e)))?;
+                let mut segments = vec![(metadata::APP1, metadata::exif(capture))];
+                if let Some(profile) = profile {
+                    match metadata::icc_segments(profile) {
+                        Some(payloads) => {
+                            segments.extend(payloads.into_iter().map(|p| (metadata::APP2, p)))
+                        }
+                        None => eprintln!(
+                            "Warning: the ICC profile is too large to embed in JPEG, skipping it"
+                        ),
+                    }
+                }
+                metadata::insert_jpeg_segments(&mut jpeg, &segments);
+                writer.write_all(&jpeg).map_err(|e| Error::write(e, path))?;
+            }
+            Format::Bmp => DynamicImage::ImageRgb8(image)
+                .write_to(&mut writer, format.image_format())
+                .map_err(|e| match e {
+                    image::ImageError::IoError(e) => Error::write(e, path),
+                    e => Error::EncodingError(Box::new(e)),
+                })?,
+        }
+
+        let file = writer
+            .into_inner()
+            .map_err(|e| Error::write(e.into_error(), path))?;
+        file.sync_all().map_err(|e| Error::write(e, path))
+    }
+
+    /// Adds an alpha channel to the image, which makes the pixels outside of
+    /// the given shape transparent.
+    fn apply_shape(image: RgbImage, shape: &[Rect]) -> RgbaImage {
+        let mut image = DynamicImage::ImageRgb8(image).to_rgba();
+        for pixel in image.pixels_mut() {
+            pixel.0[3] = 0;
+        }
+
+        let (width, height) = image.dimensions();
+        for rect in shape {
+            let x = rect.x.max(0) as u32;
+            let y = rect.y.max(0) as u32;
+            let right = (i64::from(rect.x) + i64::from(rect.width)).clamp(0, i64::from(width));
+            let bottom = (i64::from(rect.y) + i64::from(rect.height)).clamp(0, i64::from(height));
+
+            for y in y..bottom as u32 {
+                for x in x..right as u32 {
+                    image.get_pixel_mut(x, y).0[3] = 255;
+                }
+            }
+        }
+        image
+    }
+
+    /// Encodes the image as PNG, with the colour profile and text chunks
+    /// describing the capture placed before the image data. Images with an
+    /// alpha channel are stored as RGBA, all others as RGB.
+    fn encode_png<W: Write>(
+        writer: W,
+        image: DynamicImage,
+        capture: &Capture,
+        profile: Option<&[u8]>,
+        srgb: bool,
+    ) -> Result<(), png::EncodingError> {
+        let (color, width, height, data) = match image {
+            DynamicImage::ImageRgba8(image) => (
+                png::ColorType::RGBA,
+                image.width(),
+                image.height(),
+                image.into_raw(),
+            ),
+            image => {
+                let image = image.to_rgb();
+                (png::ColorType::RGB, image.width(), image.height(), image.into_raw())
+            }
+        };
+
+        let mut encoder = png::Encoder::new(writer, width, height);
+        encoder
+            .set(color)
+            .set(png::BitDepth::Eight);
+
+        let mut writer = encoder.write_header()?;
+        if let Some(profile) = profile {
+            let (kind, data) = metadata::png_profile(profile);
+            writer.write_chunk(kind, &data)?;
+        } else if srgb {
+            let (kind, data) = metadata::png_srgb();
+            writer.write_chunk(kind, &data)?;
+        }
+
+        for (kind, data) in metadata::png_chunks(capture) {
+            writer.write_chunk(kind, &data)?;
+        }
+        writer.write_image_data(&data)
+    }
+
+    /// Moves the written temporary file to its final path, following the
+    /// collision policy.
+    fn persist(temp: &Path, path: &Path, on_exists: OnExists) -> Result<PathBuf, Error> {
+        Self::persist_with(temp, path, on_exists, |from, to| fs::hard_link(from, to))
+    }
+
+    /// Does the work of `persist`, creating hard links with `link`.
+    fn persist_with<F>(
+        temp: &Path,
+        path: &Path,
+        on_exists: OnExists,
+        link: F,
+    ) -> Result<PathBuf, Error>
+    where
+        F: Fn(&Path, &Path) -> io::Result<()>,
+    {
+        if on_exists == OnExists::Overwrite {
+            fs::rename(temp, path).map_err(|e| Error::write(e, path))?;
+            return Ok(path.to_path_buf());
+        }
+
+        for n in 1.. {
+            let target = if n == 1 {
+                path.to_path_buf()
+            } else {
+                Self::numbered(path, n)
+            };
+
+            // Unlike renaming, linking fails if the target already exists,
+            // so there is no window in which another file could be replaced.
+            match link(temp, &target) {
+                Ok(()) => {
+                    fs::remove_file(temp).map_err(|e| Error::write(e, path))?;
+                    return Ok(target);
+                }
+                // File systems without support for hard links fail with
+                // another error, whether the target exists or not.
+                Err(e) if e.kind() != io::ErrorKind::AlreadyExists && !target.exists() => {
+                    fs::rename(temp, &target).map_err(|e| Error::write(e, &target))?;
+                    return Ok(target);
+                }
+                Err(_) if on_exists == OnExists::Fail => {
+                    return Err(Error::FileExists(target.to_string_lossy().into_owned()));
+                }
+                Err(_) => (),
+            }
+        }
+        unreachable!()
+    }
+
+    /// Returns the path with a number appended to its name, e.g. `name-2.png`.
+    fn numbered(path: &Path, n: u32) -> PathBuf {
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let name = match path.extension() {
+            Some(ext) => format!("{}-{}.{}", stem, n, ext.to_string_lossy()),
+            None => format!("{}-{}", stem, n),
+        };
+        path.with_file_name(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates an empty directory for a test, named after it.
+    fn directory(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("xscreen-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Writes a temporary file with the given contents, as `write` would.
+    fn temp(dir: &Path, contents: &str) -> PathBuf {
+        let temp = dir.join(".shot.png.tmp");
+        fs::write(&temp, contents).unwrap();
+        temp
+    }
+
+    #[test]
+    fn numbers_names() {
+        let path = Path::new("/tmp/shot.png");
+        assert_eq!(Screenshot::numbered(path, 2), Path::new("/tmp/shot-2.png"));
+        assert_eq!(
+            Screenshot::numbered(Path::new("shot"), 3),
+            Path::new("shot-3")
+        );
+        assert_eq!(
+            Screenshot::numbered(Path::new("a.b.png"), 2),
+            Path::new("a.b-2.png")
+        );
+    }
+
+    #[test]
+    fn increments_taken_names() {
+        let dir = directory("increment");
+        let path = dir.join("shot.png");
+        fs::write(&path, "old").unwrap();
+        fs::write(dir.join("shot-2.png"), "old").unwrap();
+
+        let temp = temp(&dir, "new");
+        let saved = Screenshot::persist(&temp, &path, OnExists::Increment).unwrap();
+        assert_eq!(saved, dir.join("shot-3.png"));
+        assert_eq!(fs::read_to_string(&saved).unwrap(), "new");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+        assert!(!temp.exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn overwrites_when_asked() {
+        let dir = directory("overwrite");
+        let path = dir.join("shot.png");
+        fs::write(&path, "old").unwrap();
+
+        let temp = temp(&dir, "new");
+        let saved = Screenshot::persist(&temp, &path, OnExists::Overwrite).unwrap();
+        assert_eq!(saved, path);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn fails_on_taken_names() {
+        let dir = directory("fail");
+        let path = dir.join("shot.png");
+        fs::write(&path, "old").unwrap();
+
+        let temp = temp(&dir, "new");
+        match Screenshot::persist(&temp, &path, OnExists::Fail) {
+            Err(Error::FileExists(name)) => assert_eq!(Path::new(&name), path),
+            _ => panic!("an existing file was replaced"),
+        }
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn keeps_free_names() {
+        let dir = directory("free");
+        let path = dir.join("shot.png");
+
+        let temp = temp(&dir, "new");
+        let saved = Screenshot::persist(&temp, &path, OnExists::Fail).unwrap();
+        assert_eq!(saved, path);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn applies_the_policy_without_hard_links() {
+        let dir = directory("no-links");
+        let path = dir.join("shot.png");
+        fs::write(&path, "old").unwrap();
+        let unsupported = |_: &Path, _: &Path| Err(io::ErrorKind::PermissionDenied.into());
+
+        let temp = temp(&dir, "new");
+        match Screenshot::persist_with(&temp, &path, OnExists::Fail, unsupported) {
+            Err(Error::FileExists(name)) => assert_eq!(Path::new(&name), path),
+            _ => panic!("an existing file was replaced"),
+        }
+
+        let saved = Screenshot::persist_with(&temp, &path, OnExists::Increment, unsupported);
+        assert_eq!(saved.unwrap(), dir.join("shot-2.png"));
+        assert_eq!(fs::read_to_string(dir.join("shot-2.png")).unwrap(), "new");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+
+        fs::remove_dir_all(&dir).unwrap();
     }
 }
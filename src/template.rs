@@ -0,0 +1,229 @@
+use crate::capture::Capture;
+use crate::config;
+use crate::errors::Error;
+use crate::screenshot::Format;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+/// Format of the `{date}` placeholder if none is given.
+const DATE_FORMAT: &str = "%Y-%m-%d %H-%M-%S";
+
+/// Longest a substituted value may be, in characters. Keeps long window
+/// titles from exceeding the filename limit of most file systems.
+const MAX_VALUE_LENGTH: usize = 100;
+
+/// A template for the paths of screenshots, such as
+/// `~/shots/{date:%Y/%m}/{wm_class}-{title}-{w}x{h}-{n}.{ext}`.
+///
+/// Supported placeholders:
+/// * `{date}` or `{date:FORMAT}`: the time of the capture as a `strftime` format
+/// * `{title}`, `{wm_class}` and `{id}`: the captured window, if any
+/// * `{x}`, `{y}`, `{w}` and `{h}`: the captured area, relative to the root
+/// * `{mode}`: `fullscreen`, `region` or `window`
+/// * `{n}`: the lowest number for which the file does not exist yet
+/// * `{ext}`: the extension of the image format
+///
+/// Braces are escaped by doubling them, i.e. `{{` and `}}`.
+pub struct Template<'a> {
+    template: &'a str,
+}
+
+impl<'a> Template<'a> {
+    pub fn new(template: &'a str) -> Self {
+        Self { template }
+    }
+
+    /// Expands the template for the given capture. Relative paths are
+    /// placed in `directory`.
+    pub fn path(
+        &self,
+        directory: &Path,
+        capture: &Capture,
+        format: Format,
+    ) -> Result<PathBuf, Error> {
+        for n in 1.. {
+            let mut counted = false;
+            let path = self.render(capture, format, n, &mut counted)?;
+            let expanded = config::expand_home(&path).ok_or(Error::InvalidPath(path))?;
+            let path = directory.join(expanded);
+
+            if !counted || !path.exists() {
+                return Ok(path);
+            }
+        }
+        unreachable!()
+    }
+
+    /// Substitutes the placeholders of the template. Sets `counted` if the
+    /// path contains `{n}`, so it has to be numbered.
+    fn render(
+        &self,
+        capture: &Capture,
+        format: Format,
+        n: u32,
+        counted: &mut bool,
+    ) -> Result<String, Error> {
+        let mut path = String::new();
+        let mut rest = self.template;
+
+        while let Some(start) = rest.find(['{', '}']) {
+            path.push_str(&rest[..start]);
+            let tail = &rest[start..];
+
+            if tail.starts_with("{{") || tail.starts_with("}}") {
+                path.push_str(&tail[..1]);
+                rest = &tail[2..];
+                continue;
+            }
+
+            let end = match tail.find('}') {
+                Some(end) if tail.starts_with('{') => end,
+                _ => return Err(self.invalid("unmatched brace")),
+            };
+            let placeholder = &tail[1..end];
+            *counted |= placeholder == "n";
+            path.push_str(&self.value(placeholder, capture, format, n)?);
+            rest = &tail[end + 1..];
+        }
+
+        path.push_str(rest);
+        Ok(path)
+    }
+
+    /// Returns the value of a single placeholder, without its braces.
+    fn value(
+        &self,
+        placeholder: &str,
+        capture: &Capture,
+        format: Format,
+        n: u32,
+    ) -> Result<String, Error> {
+        let mut parts = placeholder.splitn(2, ':');
+        let name = parts.next().unwrap_or_default();
+        let argument = parts.next();
+        let window = capture.window.as_ref();
+        let rect = &capture.rect;
+
+        let value = match (name, argument) {
+            // Dates are not sanitised, so they can be used to create
+            // directories, e.g. `{date:%Y/%m}`.
+            ("date", _) => {
+                let mut date = String::new();
+                write!(date, "{}", capture.time.format(argument.unwrap_or(DATE_FORMAT)))
+                    .map_err(|_| self.invalid("invalid date format"))?;
+                return Ok(date);
+            }
+            ("title", None) => window.and_then(|w| w.title.clone()).unwrap_or_default(),
+            ("wm_class", None) => window.and_then(|w| w.class.clone()).unwrap_or_default(),
+            ("id", None) => window.map(|w| format!("{:#x}", w.id)).unwrap_or_default(),
+            ("x", None) => rect.x.to_string(),
+            ("y", None) => rect.y.to_string(),
+            ("w", None) => rect.width.to_string(),
+            ("h", None) => rect.height.to_string(),
+            ("mode", None) => capture.mode.name().to_string(),
+            ("n", None) => n.to_string(),
+            ("ext", None) => format.extension().to_string(),
+            _ => return Err(self.invalid(&format!("unknown placeholder {{{}}}", placeholder))),
+        };
+
+        Ok(sanitise(&value))
+    }
+
+    fn invalid(&self, reason: &str) -> Error {
+        Error::InvalidConfig(format!("Invalid template `{}`: {}", self.template, reason))
+    }
+}
+
+/// Makes a substituted value safe to use as (part of) a filename by
+/// replacing path separators and control characters.
+fn sanitise(value: &str) -> String {
+    let value: String = value
+        .trim()
+        .chars()
+        .take(MAX_VALUE_LENGTH)
+        .map(|c| if c == '/' || c.is_control() { '_' } else { c })
+        .collect();
+
+    match value.as_str() {
+        "." | ".." => value.replace('.', "_"),
+        _ => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn render(template: &str) -> Result<String, Error> {
+        Template::new(template).render(&Capture::example(), Format::Png, 1, &mut false)
+    }
+
+    #[test]
+    fn substitutes_placeholders() {
+        let path = render("{wm_class}-{title}-{id}-{w}x{h}+{x}+{y}-{mode}.{ext}").unwrap();
+        assert_eq!(
+            path,
+            "Alacritty-Terminal-0x1e00003-640x480+10+20-window.png"
+        );
+    }
+
+    #[test]
+    fn formats_dates() {
+        assert_eq!(render("{date}").unwrap(), "2019-06-01 12-00-00");
+        assert_eq!(render("{date:%Y/%m}/shot").unwrap(), "2019/06/shot");
+    }
+
+    #[test]
+    fn escapes_braces() {
+        assert_eq!(render("{{{mode}}}").unwrap(), "{window}");
+        assert_eq!(render("a}}b{{c").unwrap(), "a}b{c");
+    }
+
+    #[test]
+    fn rejects_invalid_templates() {
+        for template in &["{mode", "mode}", "{unknown}", "{title:arg}"] {
+            assert!(
+                matches!(render(template), Err(Error::InvalidConfig(_))),
+                "{} was accepted",
+                template
+            );
+        }
+    }
+
+    #[test]
+    fn sanitises_values() {
+        assert_eq!(sanitise(" a/b\tc "), "a_b_c");
+        assert_eq!(sanitise("."), "_");
+        assert_eq!(sanitise(".."), "__");
+        assert_eq!(sanitise(&"x".repeat(200)).len(), MAX_VALUE_LENGTH);
+    }
+
+    #[test]
+    fn numbers_taken_paths() {
+        let dir = std::env::temp_dir().join(format!("xscreen-template-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let template = Template::new("shot-{n}.{ext}");
+        let capture = Capture::example();
+
+        let first = template.path(&dir, &capture, Format::Png).unwrap();
+        assert_eq!(first, dir.join("shot-1.png"));
+        fs::write(&first, b"").unwrap();
+        let second = template.path(&dir, &capture, Format::Png).unwrap();
+        assert_eq!(second, dir.join("shot-2.png"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn does_not_number_escaped_placeholders() {
+        let dir = std::env::temp_dir().join(format!("xscreen-escaped-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("shot{n}.png"), b"").unwrap();
+
+        let path = Template::new("shot{{n}}.{ext}").path(&dir, &Capture::example(), Format::Png);
+        assert_eq!(path.unwrap(), dir.join("shot{n}.png"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,176 +1,407 @@
+use crate::clients;
 use crate::errors::Error;
-use crate::overlay::Overlay;
+use crate::keymap::{Action, Keymap};
+use crate::overlay::{Overlay, Style};
+use std::collections::HashMap;
+use x11::xlib::{XConfigureEvent, XDestroyWindowEvent, XMapEvent, XUnmapEvent};
 use xlib::{Display, EventKind, Rect, Window};
 
-const MOUSE_LEFT: u32 = 1;
+/// What the picker knows about a window that only changes along with the
+/// window itself, kept while the list of windows is updated. The title is
+/// not kept, as it changes without the window being reconfigured.
+struct Details {
+    /// The ancestor of the window that is a child of the root, whose events
+    /// the picker receives.
+    frame: u64,
+    /// The bounding shape of the window relative to its origin.
+    shape: Option<Vec<Rect>>,
+}
+
+impl Details {
+    fn new(display: &Display, window: &Window) -> Self {
+        Self {
+            frame: clients::top_level(display, window).as_raw(),
+            shape: display.get_shape(window),
+        }
+    }
+}
+
+/// A window that can be picked, along with how it is highlighted.
+#[derive(Clone)]
+struct Target {
+    window: Window,
+    /// Position and size of the window on the screen.
+    rect: Rect,
+    /// The bounding shape of the window on the screen, if it is shaped.
+    shape: Option<Vec<Rect>>,
+}
+
+impl Target {
+    fn new(display: &Display, window: Window, details: &Details) -> Self {
+        let rect = clients::screen_rect(display, &window);
+        let shape = details.shape.as_ref().map(|rects| {
+            rects
+                .iter()
+                .map(|r| Rect {
+                    x: rect.x + r.x,
+                    y: rect.y + r.y,
+                    ..*r
+                })
+                .collect()
+        });
+
+        Self {
+            window,
+            rect,
+            shape,
+        }
+    }
+
+    /// Returns the title, class and size of the window, read when it is
+    /// highlighted so that it reflects the current title.
+    fn caption(&self, display: &Display) -> String {
+        let size = format!("{}x{}", self.rect.width, self.rect.height);
+        let title = clients::title(display, &self.window);
+        let class = clients::class(display, &self.window);
+
+        [title, class, Some(size)]
+            .iter()
+            .flatten()
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(" - ")
+    }
+}
 
 pub struct WindowCapture<'a> {
     display: &'a Display,
     overlay: Overlay<'a>,
+    keymap: &'a Keymap,
+    include_types: Vec<String>,
+    details: HashMap<u64, Details>,
 }
 
 impl<'a> WindowCapture<'a> {
-    pub fn new(display: &'a Display) -> Self {
-        let overlay = Overlay::new(display);
-        Self { display, overlay }
-    }
-
-    /// Checks if the given window is visible, i.e. whether it is shown on
-    /// any of the monitors. Returns `None` if the window is either
-    /// completely covered by another window, or if the window is visible
-    /// on an underlying workspace/virtual desktop.
-    // ? other WMs may have a different ordering of the wm_state fields(?)
-    fn is_visible(&self, window: &Window) -> bool {
-        let atom = self.display.intern_atom("WM_STATE", false);
-        let mut actual_type = 0;
-        let mut format = 0;
-        let mut length = 0;
-        let mut bytes_after_return = 0;
-        let mut ptr = std::ptr::null_mut();
-        unsafe {
-            x11::xlib::XGetWindowProperty(
-                self.display.as_raw(),
-                window.as_raw(),
-                atom.0,
-                0,    // offset
-                1024, // 32-bit multiples of data to be read
-                0,    // delete
-                0,    // req type
-                &mut actual_type,
-                &mut format,
-                &mut length,
-                &mut bytes_after_return,
-                &mut ptr,
-            );
-
-            let slice = std::slice::from_raw_parts(ptr as *mut i8, length as usize);
-            let visible = if length > 0 { slice[0] == 1 } else { false };
-            x11::xlib::XFree(ptr as *mut std::ffi::c_void);
-            visible
-        }
+    pub fn new(display: &'a Display, keymap: &'a Keymap, style: Style) -> Result<Self, Error> {
+        let overlay = Overlay::new(display, style)?;
+        Ok(Self {
+            display,
+            overlay,
+            keymap,
+            include_types: Vec::new(),
+            details: HashMap::new(),
+        })
     }
 
-    /// Returns a list of all children windows that are visible.
-    /// Windows that do not have this attribute are not managed by the wm,
-    /// or is often a container of some sort owned by the window manager.
-    fn get_children(&self, window: &Window) -> Vec<Window> {
-        let mut root = 0;
-        let mut parent = 0;
-        let mut ptr = std::ptr::null_mut();
-        let mut length = 0;
-
-        let array = unsafe {
-            x11::xlib::XQueryTree(
-                self.display.as_raw(),
-                window.as_raw(),
-                &mut root,
-                &mut parent,
-                &mut ptr,
-                &mut length,
-            );
-
-            std::slice::from_raw_parts(ptr, length as usize)
-        };
+    /// Includes windows of the given EWMH types, such as docks, which are
+    /// skipped by default. See `clients::EXCLUDED_TYPES`.
+    pub fn include_types(mut self, types: &[String]) -> Self {
+        self.include_types = types.to_vec();
+        self
+    }
 
-        let windows: Vec<Window> = array
-            .iter()
-            .rev()
-            .map(|w| Window::from_raw(self.display, *w))
-            .collect();
+    /// Returns the windows that can be picked from top to bottom.
+    fn windows(&mut self) -> Vec<Target> {
+        clients::pickable_windows(self.display, &self.include_types)
+            .into_iter()
+            .map(|w| self.target(w))
+            .collect()
+    }
 
-        unsafe {
-            x11::xlib::XFree(ptr as *mut std::ffi::c_void);
-        }
+    /// Returns the target of the given window, reading the details of the
+    /// window unless they are known already.
+    fn target(&mut self, window: Window) -> Target {
+        let display = self.display;
+        let details = self
+            .details
+            .entry(window.as_raw())
+            .or_insert_with(|| Details::new(display, &window));
+        Target::new(display, window, details)
+    }
 
-        windows
+    /// Forgets the details of the windows an event was reported for, i.e.
+    /// of the window itself and of the windows inside it.
+    fn invalidate(&mut self, id: u64) {
+        self.details
+            .retain(|window, details| *window != id && details.frame != id);
     }
 
-    /// Scans through the first two depth layers of all windows (i.e. parents and
-    /// their respective children). Filters out windows that are not visible on
-    /// the current workspace/virtual desktop.
-    fn get_all_windows(&self) -> Vec<Window> {
-        let mut windows: Vec<Window> = Vec::new();
-        let parents = self.get_children(&self.overlay.root);
+    /// Moves and restacks the windows inside a child of the root that was
+    /// reconfigured, without reading the whole list again. Returns false if
+    /// the new position in the stacking order is not known, i.e. the window
+    /// was placed above one that is not in the list.
+    fn configure(&mut self, windows: &mut Vec<Target>, event: &XConfigureEvent) -> bool {
+        let details = &self.details;
+        let frame = |target: &Target| details.get(&target.window.as_raw()).map(|d| d.frame);
+        let (moved, mut rest): (Vec<Target>, Vec<Target>) = windows
+            .drain(..)
+            .partition(|t| frame(t) == Some(event.window));
+        let index = match event.above {
+            0 => Some(rest.len()),
+            above => rest.iter().position(|t| frame(t) == Some(above)),
+        };
 
-        for w in parents {
-            for c in self.get_children(&w) {
-                if self.is_visible(&c) {
-                    windows.push(c);
-                }
+        let index = match index {
+            Some(index) if !moved.is_empty() => index,
+            index => {
+                // Windows that are not in the list stay out of it.
+                let known = moved.is_empty() || index.is_some();
+                rest.extend(moved);
+                *windows = rest;
+                return known;
+            }
+        };
+
+        self.invalidate(event.window);
+        let moved: Vec<Target> = moved.into_iter().map(|t| self.target(t.window)).collect();
+        rest.splice(index..index, moved);
+        *windows = rest;
+        true
+    }
+
+    /// Applies an event about a child of the root to the list of windows.
+    /// Returns false if the list has to be read again, i.e. if a window was
+    /// mapped, unmapped or destroyed, or restacked to an unknown position.
+    fn update(&mut self, windows: &mut Vec<Target>, kind: &EventKind) -> bool {
+        match kind {
+            EventKind::Configure(e) if !self.overlay.is_overlay(e.window) => {
+                self.configure(windows, e)
             }
+            EventKind::Map(XMapEvent { window: id, .. })
+            | EventKind::Unmap(XUnmapEvent { window: id, .. })
+            | EventKind::DestroyWindow(XDestroyWindowEvent { window: id, .. })
+                if !self.overlay.is_overlay(*id) =>
+            {
+                self.invalidate(*id);
+                false
+            }
+            _ => true,
+        }
+    }
+
+    /// Checks whether the given point lies inside the rectangle.
+    fn contains(r: &Rect, x: i32, y: i32) -> bool {
+        x > r.x && x < (r.x + r.width as i32) && y > r.y && y < (r.y + r.height as i32)
+    }
+
+    /// Checks whether the given point lies inside the shape of the window,
+    /// or its rectangle if it is not shaped.
+    fn hit(target: &Target, x: i32, y: i32) -> bool {
+        if !Self::contains(&target.rect, x, y) {
+            return false;
         }
 
-        windows
+        target.shape.as_ref().is_none_or(|shape| {
+            shape.iter().any(|r| {
+                x >= r.x && x < r.x + r.width as i32 && y >= r.y && y < r.y + r.height as i32
+            })
+        })
+    }
+
+    /// Returns the topmost window that contains the given point. Clicks
+    /// outside the shape of a shaped window fall through to the windows
+    /// below it.
+    fn window_at(windows: &[Target], x: i32, y: i32) -> Option<&Target> {
+        windows.iter().find(|t| Self::hit(t, x, y))
+    }
+
+    /// Returns the topmost sub-window of the given window that contains the
+    /// given point. Windows without contents, i.e. InputOnly windows, are
+    /// skipped.
+    fn child_at(&mut self, window: &Window, x: i32, y: i32) -> Option<Target> {
+        let child = clients::get_children(self.display, window)
+            .into_iter()
+            .filter(|child| {
+                child
+                    .get_attributes()
+                    .is_some_and(|a| a.viewable && !a.input_only)
+            })
+            .find(|child| Self::contains(&clients::screen_rect(self.display, child), x, y))?;
+        Some(self.target(child))
+    }
+
+    /// Returns the window after the given one in stacking order, i.e. the
+    /// one below it, or the one above it if `backwards` is set. Wraps
+    /// around at either end, and starts at the topmost window if the given
+    /// window is not in the list. `windows` must not be empty.
+    fn cycle<'w>(windows: &'w [Target], window: &Window, backwards: bool) -> &'w Target {
+        let len = windows.len();
+        let index = match windows
+            .iter()
+            .position(|t| t.window.as_raw() == window.as_raw())
+        {
+            Some(i) if backwards => (i + len - 1) % len,
+            Some(i) => (i + 1) % len,
+            None if backwards => len - 1,
+            None => 0,
+        };
+        &windows[index]
+    }
+
+    /// Highlights the given window, replacing the previous highlight.
+    fn highlight(&mut self, target: &Target) {
+        let caption = target.caption(self.display);
+        self.overlay.clear();
+        self.overlay
+            .draw_window(&target.rect, target.shape.as_deref(), &caption);
     }
 
     /// This function is responsible for drawing the rectangle that highlights
     /// the selected window. It also handles the main event loop of the UI.
-    /// It loops through all open windows (from bottom to top) and compares the
-    /// coordinates of said windows to that of the cursor.
+    /// It loops through all open windows (from top to bottom) and compares the
+    /// coordinates of said windows to that of the cursor. The list of windows
+    /// is updated whenever a window is mapped, unmapped, moved or restacked.
+    /// The scroll wheel walks down and up the sub-windows under the cursor,
+    /// and Tab cycles through the windows in stacking order.
     /// Returns the `Window` structure of the selected window.
     /// May return `None` if the capture was aborted.
     // ? Focus event? May generate if we don't own the mouse input events
     pub fn show(&mut self) -> Result<Window, Error> {
-        self.overlay.show(false);
-        let mut window = self.overlay.root;
-        let windows = self.get_all_windows();
-        let rects: Vec<_> = windows
-            .iter()
-            .map(|w| {
-                let rect = w.get_rect();
-                let (x, y) = self.display.translate_coordinates(&w, 0, 0);
-                (
-                    w,
-                    Rect {
-                        width: rect.width,
-                        height: rect.height,
-                        x,
-                        y,
-                    },
-                )
-            })
-            .collect();
+        let mut windows = self.windows();
+        if windows.is_empty() {
+            return Err(Error::NoMatchingWindow(String::from(
+                "no visible windows to pick from",
+            )));
+        }
+
+        self.overlay.show(false)?;
+        let root = self.overlay.root;
+        self.display
+            .select_input(&root, xlib::SUBSTRUCTURE_NOTIFY_MASK);
+        let mut window = root;
+        // The windows containing the highlighted sub-window, starting with
+        // the top-level window. Empty unless a sub-window was chosen.
+        let mut parents: Vec<Window> = Vec::new();
+        // Whether the highlighted window was chosen with the keyboard, in
+        // which case it stays highlighted until the pointer moves.
+        let mut keyboard = false;
 
         loop {
             let event = self.overlay.next_event();
 
             match event.get_kind() {
-                // Either the primary or secondary mouse button was pressed
-                EventKind::ButtonPress(event) => {
-                    if event.button == MOUSE_LEFT {
-                        return Ok(window);
+                // A mouse button that we monitor was pressed.
+                EventKind::ButtonPress(event) => match self.keymap.button(event.button) {
+                    Some(Action::Select) => return Ok(window),
+                    Some(Action::Cancel) => break,
+                    Some(Action::Child) if window.as_raw() != root.as_raw() => {
+                        if let Some(child) = self.child_at(&window, event.x_root, event.y_root) {
+                            parents.push(window);
+                            window = child.window;
+                            self.highlight(&child);
+                        }
                     }
-                }
+                    Some(Action::Parent) => {
+                        if let Some(parent) = parents.pop() {
+                            window = parent;
+                            let target = self.target(window);
+                            self.highlight(&target);
+                        }
+                    }
+                    _ => (),
+                },
 
                 // A key event that we monitor was triggered.
-                EventKind::KeyPress(event) => match event.keycode {
-                    // escape, q
-                    9 | 24 => break,
-
-                    // ignore the rest
+                EventKind::KeyPress(event) => match self.keymap.key(event) {
+                    Some(Action::Cancel) => break,
+                    Some(Action::Confirm) => return Ok(window),
+                    Some(Action::Next) if !windows.is_empty() => {
+                        let backwards = event.state & xlib::SHIFT_MASK != 0;
+                        let current = parents.first().unwrap_or(&window);
+                        let target = Self::cycle(&windows, current, backwards).clone();
+                        window = target.window;
+                        parents.clear();
+                        keyboard = true;
+                        self.highlight(&target);
+                    }
                     _ => (),
                 },
 
                 // Cursor moved; check its position and redraw the overlay.
                 EventKind::Motion(cursor) => {
-                    for (w, r) in &rects {
-                        if cursor.x > r.x
-                            && cursor.x < (r.x + r.width as i32)
-                            && cursor.y > r.y
-                            && cursor.y < (r.y + r.height as i32)
-                        {
-                            window = **w;
-                            self.overlay.clear();
-                            self.overlay.draw_rect(&r);
-                            break;
+                    keyboard = false;
+
+                    // A sub-window stays highlighted until the cursor leaves it.
+                    if !parents.is_empty() {
+                        let rect = clients::screen_rect(self.display, &window);
+                        if Self::contains(&rect, cursor.x, cursor.y) {
+                            continue;
+                        }
+                        window = parents[0];
+                        parents.clear();
+                        let target = self.target(window);
+                        self.highlight(&target);
+                    }
+
+                    if let Some(target) = Self::window_at(&windows, cursor.x, cursor.y) {
+                        if target.window.as_raw() != window.as_raw() {
+                            window = target.window;
+                            let target = target.clone();
+                            self.highlight(&target);
                         }
                     }
                 }
 
-                // The window was destroyed by external means.
-                EventKind::DestroyWindow(_) => {
+                // The overlay was destroyed by external means.
+                EventKind::DestroyWindow(e) if self.overlay.is_overlay(e.window) => {
                     return Err(Error::WindowDestroyed);
                 }
+
+                // Another window was opened, closed, moved or restacked. The
+                // events that are already queued are handled along with it,
+                // so that the list is read at most once.
+                EventKind::Configure(XConfigureEvent { window: id, .. })
+                | EventKind::Map(XMapEvent { window: id, .. })
+                | EventKind::Unmap(XUnmapEvent { window: id, .. })
+                | EventKind::DestroyWindow(XDestroyWindowEvent { window: id, .. })
+                    if !self.overlay.is_overlay(*id) =>
+                {
+                    let mut current = self.update(&mut windows, event.get_kind());
+                    while let Some(event) = self.overlay.next_structure_event() {
+                        match event.get_kind() {
+                            EventKind::DestroyWindow(e) if self.overlay.is_overlay(e.window) => {
+                                return Err(Error::WindowDestroyed);
+                            }
+                            EventKind::Configure(e) if !current => self.invalidate(e.window),
+                            kind => current = self.update(&mut windows, kind) && current,
+                        }
+                    }
+                    if !current {
+                        windows = self.windows();
+                    }
+
+                    if !parents.is_empty() {
+                        if window.get_attributes().is_some_and(|a| a.viewable) {
+                            let target = self.target(window);
+                            self.highlight(&target);
+                            continue;
+                        }
+                        window = parents[0];
+                        parents.clear();
+                    }
+
+                    let current = windows
+                        .iter()
+                        .find(|t| t.window.as_raw() == window.as_raw());
+                    let cursor = self.display.query_pointer(&root);
+                    let hovered = match current {
+                        Some(current) if keyboard => Some(current),
+                        _ => Self::window_at(&windows, cursor.x, cursor.y).or(current),
+                    }
+                    .cloned();
+                    keyboard = keyboard && current.is_some();
+
+                    match hovered {
+                        Some(target) => {
+                            window = target.window;
+                            self.highlight(&target);
+                        }
+                        None => {
+                            window = root;
+                            self.overlay.clear();
+                        }
+                    }
+                }
                 _ => (),
             }
         }
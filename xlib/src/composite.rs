@@ -0,0 +1,55 @@
+use crate::dl::Library;
+use crate::{Display, Window, XDisplay};
+use std::sync::OnceLock;
+use x11::xlib;
+
+static LIBRARY: OnceLock<Option<Library>> = OnceLock::new();
+
+type NameWindowPixmap = unsafe extern "C" fn(*mut xlib::Display, xlib::Window) -> xlib::Pixmap;
+
+/// A pixmap, which is freed when dropped.
+pub struct Pixmap {
+    display: XDisplay,
+    inner: xlib::Pixmap,
+}
+
+impl Pixmap {
+    pub const fn as_raw(&self) -> xlib::Pixmap {
+        self.inner
+    }
+}
+
+impl Drop for Pixmap {
+    // XFreePixmap
+    fn drop(&mut self) {
+        unsafe {
+            xlib::XFreePixmap(self.display, self.inner);
+        }
+    }
+}
+
+impl Display {
+    // XCompositeNameWindowPixmap
+    // Returns the off-screen pixmap the compositor renders a redirected
+    // top-level window into, which holds its contents even while it is
+    // covered by other windows. The pixmap includes the border of the
+    // window. Returns `None` if libXcomposite is unavailable. Naming a
+    // window that is not redirected fails, after which reading from the
+    // pixmap fails as well.
+    pub fn name_window_pixmap(&self, window: &Window) -> Option<Pixmap> {
+        let library = Library::load(&LIBRARY, b"libXcomposite.so.1\0")?;
+
+        unsafe {
+            let name = library.symbol::<NameWindowPixmap>(b"XCompositeNameWindowPixmap\0")?;
+            let pixmap = name(self.as_raw(), window.as_raw());
+            if pixmap == 0 {
+                return None;
+            }
+
+            Some(Pixmap {
+                display: self.as_raw(),
+                inner: pixmap,
+            })
+        }
+    }
+}
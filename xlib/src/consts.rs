@@ -56,3 +56,13 @@ pub const DIRECT_COLOR: i32 = 5;
 pub const REVERT_TO_NONE: i32 = 0;
 pub const REVERT_TO_POINTER_ROOT: i32 = 1;
 pub const REVERT_TO_PARENT: i32 = 2;
+
+// key and button masks
+pub const SHIFT_MASK: u32 = 0x0001;
+pub const LOCK_MASK: u32 = 0x0002;
+pub const CONTROL_MASK: u32 = 0x0004;
+pub const MOD1_MASK: u32 = 0x0008;
+pub const MOD2_MASK: u32 = 0x0010;
+pub const MOD3_MASK: u32 = 0x0020;
+pub const MOD4_MASK: u32 = 0x0040;
+pub const MOD5_MASK: u32 = 0x0080;
@@ -1,6 +1,6 @@
 extern crate libc;
 use crate::{Event, GContext, Rect, Window, XDisplay, XError, XEvent, XResult, XWindow};
-use std::ffi::CString;
+use std::ffi::{CStr, CString};
 use std::mem;
 use std::ptr;
 use x11::xlib;
@@ -50,6 +50,23 @@ impl Display {
         Ok(Self { inner: display })
     }
 
+    // XSetErrorHandler
+    // Replaces the default error handler, which exits the process, with one
+    // that ignores errors. Failed requests then return their usual failure
+    // values, e.g. for windows that were destroyed in the meantime.
+    pub fn ignore_errors(&self) {
+        unsafe extern "C" fn handler(
+            _: *mut xlib::Display,
+            _: *mut xlib::XErrorEvent,
+        ) -> std::os::raw::c_int {
+            0
+        }
+
+        unsafe {
+            xlib::XSetErrorHandler(Some(handler));
+        }
+    }
+
     // XDefaultRootWindow
     pub fn default_window(&self) -> Window {
         let window = unsafe { xlib::XDefaultRootWindow(self.inner) };
@@ -135,6 +152,20 @@ impl Display {
         Atom(unsafe { xlib::XInternAtom(self.inner, c_str.as_ptr(), only_if_exists.into()) })
     }
 
+    // XGetAtomName
+    pub fn get_atom_name(&self, atom: Atom) -> Option<String> {
+        unsafe {
+            let ptr = xlib::XGetAtomName(self.inner, atom.0);
+            if ptr.is_null() {
+                return None;
+            }
+
+            let name = CStr::from_ptr(ptr).to_string_lossy().into_owned();
+            xlib::XFree(ptr as *mut std::ffi::c_void);
+            Some(name)
+        }
+    }
+
     // XGetSelectionOwner
     pub fn get_selection_owner(&self, atom: Atom) -> u64 {
         unsafe { xlib::XGetSelectionOwner(self.inner, atom.0) }
@@ -340,6 +371,60 @@ impl Display {
         unsafe { xlib::XDisplayHeight(self.inner, screen) }
     }
 
+    // XkbSetDetectableAutoRepeat
+    // Held keys only send a single release event once detectable
+    // auto-repeat is enabled. Returns whether the server supports it.
+    pub fn set_detectable_autorepeat(&self, detectable: bool) -> bool {
+        let mut supported = 0;
+        unsafe {
+            xlib::XkbSetDetectableAutoRepeat(self.inner, detectable as i32, &mut supported);
+        }
+        supported != 0
+    }
+
+    // XResourceManagerString, XrmGetResource
+    // Looks up a resource such as `xscreen.selectionColor` in the resource
+    // database loaded by xrdb, e.g. from `~/.Xresources`.
+    pub fn get_resource(&self, name: &str, class: &str) -> Option<String> {
+        let name = CString::new(name).ok()?;
+        let class = CString::new(class).ok()?;
+
+        unsafe {
+            let resources = xlib::XResourceManagerString(self.inner);
+            if resources.is_null() {
+                return None;
+            }
+
+            xlib::XrmInitialize();
+            let database = xlib::XrmGetStringDatabase(resources);
+            if database.is_null() {
+                return None;
+            }
+
+            let mut kind = ptr::null_mut();
+            let mut value = xlib::XrmValue {
+                size: 0,
+                addr: ptr::null_mut(),
+            };
+            let found = xlib::XrmGetResource(
+                database,
+                name.as_ptr(),
+                class.as_ptr(),
+                &mut kind,
+                &mut value,
+            );
+
+            let ret = if found != 0 && !value.addr.is_null() {
+                Some(CStr::from_ptr(value.addr).to_string_lossy().trim().to_string())
+            } else {
+                None
+            };
+
+            xlib::XrmDestroyDatabase(database);
+            ret
+        }
+    }
+
     // XNextEvent
     pub fn next_event(&self) -> Event {
         unsafe {
@@ -348,6 +433,18 @@ impl Display {
             Event::from_raw(event)
         }
     }
+
+    // XCheckWindowEvent
+    pub fn check_window_event(&self, window: &Window, event_mask: i64) -> Option<Event> {
+        unsafe {
+            let event = libc::malloc(mem::size_of::<xlib::XEvent>()) as XEvent;
+            if xlib::XCheckWindowEvent(self.inner, window.as_raw(), event_mask, event) == 0 {
+                libc::free(event as *mut libc::c_void);
+                return None;
+            }
+            Some(Event::from_raw(event))
+        }
+    }
 }
 
 impl Drop for Display {
@@ -0,0 +1,47 @@
+use std::os::raw::{c_char, c_void};
+use std::sync::OnceLock;
+
+/// A shared library loaded at runtime, so that programs using this crate
+/// are neither linked against it nor require it to be installed; the
+/// functions that use it report it as unavailable instead. Libraries are
+/// never unloaded: extensions register hooks on the display, such as the
+/// ones `XCloseDisplay` calls, which must stay mapped until the process
+/// exits.
+pub(crate) struct Library(*mut c_void);
+
+// The handle is only passed to dlsym, which is thread-safe.
+unsafe impl Send for Library {}
+unsafe impl Sync for Library {}
+
+impl Library {
+    // dlopen
+    // `name` must be null-terminated. The library is loaded on first use
+    // and kept in `cache`, which also remembers if it is unavailable.
+    pub fn load(cache: &'static OnceLock<Option<Library>>, name: &[u8]) -> Option<&'static Self> {
+        cache
+            .get_or_init(|| {
+                let library = unsafe {
+                    libc::dlopen(
+                        name.as_ptr() as *const c_char,
+                        libc::RTLD_LAZY | libc::RTLD_LOCAL | libc::RTLD_NODELETE,
+                    )
+                };
+                if library.is_null() {
+                    return None;
+                }
+                Some(Self(library))
+            })
+            .as_ref()
+    }
+
+    // dlsym
+    // `name` must be null-terminated, and `T` must be the function pointer
+    // type of the symbol.
+    pub unsafe fn symbol<T: Copy>(&self, name: &[u8]) -> Option<T> {
+        let symbol = libc::dlsym(self.0, name.as_ptr() as *const c_char);
+        if symbol.is_null() {
+            return None;
+        }
+        Some(std::mem::transmute_copy::<*mut c_void, T>(&symbol))
+    }
+}
@@ -2,6 +2,7 @@ extern crate libc;
 extern crate x11;
 
 use crate::XEvent;
+use std::ffi::CString;
 use x11::xlib;
 
 // give them better names? CreateNotify instead of CreateWindow?
@@ -121,3 +122,20 @@ impl Drop for Event {
         unsafe { libc::free(self._inner as *mut libc::c_void) };
     }
 }
+
+// XLookupKeysym
+pub fn lookup_keysym(event: &xlib::XKeyEvent, index: i32) -> u64 {
+    let mut event = *event;
+    unsafe { xlib::XLookupKeysym(&mut event, index) }
+}
+
+// XStringToKeysym
+pub fn string_to_keysym(name: &str) -> Option<u64> {
+    let c_str = CString::new(name).ok()?;
+    let keysym = unsafe { xlib::XStringToKeysym(c_str.as_ptr()) };
+
+    if keysym == 0 {
+        return None;
+    }
+    Some(keysym)
+}
@@ -0,0 +1,86 @@
+use crate::{Display, GContext, XDisplay};
+use std::ffi::CString;
+use std::os::raw::{c_char, c_int};
+use x11::xlib;
+
+/// A core X font, which is freed when dropped. Core fonts only cover
+/// Latin-1, so text is given as Latin-1 bytes.
+pub struct Font {
+    display: XDisplay,
+    inner: *mut xlib::XFontStruct,
+}
+
+impl Font {
+    // XLoadQueryFont
+    pub fn load(display: &Display, name: &str) -> Option<Self> {
+        let c_str = CString::new(name).ok()?;
+        let font = unsafe { xlib::XLoadQueryFont(display.as_raw(), c_str.as_ptr()) };
+
+        if font.is_null() {
+            return None;
+        }
+        Some(Self {
+            display: display.as_raw(),
+            inner: font,
+        })
+    }
+
+    /// Returns the height of the font above the baseline.
+    pub fn ascent(&self) -> i32 {
+        unsafe { (*self.inner).ascent }
+    }
+
+    /// Returns the height of the font below the baseline.
+    pub fn descent(&self) -> i32 {
+        unsafe { (*self.inner).descent }
+    }
+
+    // XTextWidth
+    pub fn text_width(&self, text: &[u8]) -> i32 {
+        unsafe {
+            xlib::XTextWidth(
+                self.inner,
+                text.as_ptr() as *const c_char,
+                text.len() as c_int,
+            )
+        }
+    }
+
+    pub fn as_raw(&self) -> *mut xlib::XFontStruct {
+        self.inner
+    }
+}
+
+impl Drop for Font {
+    // XFreeFont
+    fn drop(&mut self) {
+        unsafe {
+            xlib::XFreeFont(self.display, self.inner);
+        }
+    }
+}
+
+impl Display {
+    // XDrawString
+    // `y` is the baseline of the text.
+    pub fn draw_string<T: Into<u64>>(
+        &self,
+        drawable: T,
+        gc: &GContext,
+        x: i32,
+        y: i32,
+        text: &[u8],
+    ) {
+        unsafe {
+            xlib::XDrawString(
+                self.as_raw(),
+                drawable.into(),
+                gc.as_raw(),
+                x,
+                y,
+                text.as_ptr() as *const c_char,
+                text.len() as c_int,
+            );
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{Display, Window, XGCValues, XGC};
+use crate::{Display, Font, Window, XGCValues, XGC};
 use std::mem;
 use x11::xlib;
 
@@ -50,6 +50,13 @@ impl<'a> GContext<'a> {
         }
     }
 
+    // XSetFont
+    pub fn set_font(&self, font: &Font) {
+        unsafe {
+            xlib::XSetFont(self.display.as_raw(), self.inner, (*font.as_raw()).fid);
+        }
+    }
+
     pub fn flush(&self){
         unsafe{
             xlib::XFlushGC(self.display.as_raw(), self.inner);
@@ -1,6 +1,6 @@
 // XCreateImage, XDestroyImage, XGetImage, XGetSubImage
 // XInitImage, XPutImage, XSubImage(?)
-use crate::{Display, Window, XImage};
+use crate::{Display, XImage};
 use std::{mem, slice};
 use x11::xlib;
 
@@ -11,9 +11,9 @@ pub struct Image {
 }
 
 impl Image {
-    pub fn get_image(
+    pub fn get_image<T: Into<u64>>(
         display: &Display,
-        window: &Window,
+        drawable: T,
         x: i32,
         y: i32,
         width: u32,
@@ -23,7 +23,7 @@ impl Image {
         unsafe {
             let img = xlib::XGetImage(
                 display.as_raw(),
-                window.as_raw(),
+                drawable.into(),
                 x,
                 y,
                 width,
@@ -87,14 +87,17 @@ impl VisualInfo {
         self.0
     }
 
-    pub fn from(display: &Display, screen: i32, depth: i32, color: i32) -> Self {
+    // Returns `None` if the screen has no visual of the given depth and class.
+    pub fn from(display: &Display, screen: i32, depth: i32, color: i32) -> Option<Self> {
         let mut info = Self::default();
         let mut raw = info.as_raw();
-        unsafe {
-            xlib::XMatchVisualInfo(display.as_raw(), screen, depth, color, &mut raw);
+        let found =
+            unsafe { xlib::XMatchVisualInfo(display.as_raw(), screen, depth, color, &mut raw) };
+        if found == 0 {
+            return None;
         }
         info.0 = raw;
-        info
+        Some(info)
     }
 }
 
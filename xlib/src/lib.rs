@@ -1,11 +1,16 @@
 #![allow(dead_code)]
 extern crate x11;
 
+mod composite;
 mod consts;
 mod display;
+mod dl;
 mod events;
+mod font;
 mod gcontext;
 mod image;
+mod randr;
+mod shape;
 mod window;
 
 use x11::xlib;
@@ -16,12 +21,16 @@ pub(crate) type XGCValues = *mut xlib::XGCValues;
 pub(crate) type XImage = *mut xlib::XImage;
 pub(crate) type XWindow = xlib::Window;
 
+pub use composite::Pixmap;
 pub use consts::*;
 pub use display::{Atom, CursorInfo, Display};
-pub use events::{Event, EventKind};
+pub use events::{lookup_keysym, string_to_keysym, Event, EventKind};
+pub use font::Font;
 pub use gcontext::{GCValues, GContext};
 pub use image::{Image, Visual, VisualInfo};
-pub use window::{SetWindowAttributes, Window};
+pub use randr::Monitor;
+pub use window::{SetWindowAttributes, Window, WindowAttributes};
+pub use x11::keysym;
 pub type XResult<T> = std::result::Result<T, XError>;
 
 #[derive(Debug)]
@@ -33,6 +42,20 @@ pub enum XError {
     ConnectionError,
 }
 
+impl std::fmt::Display for XError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            XError::BadAlloc => write!(f, "BadAlloc: the server failed to allocate a resource"),
+            XError::BadMatch => write!(f, "BadMatch: invalid parameter attributes"),
+            XError::BadValue => write!(f, "BadValue: parameter out of range"),
+            XError::BadWindow => write!(f, "BadWindow: no such window"),
+            XError::ConnectionError => write!(f, "unable to open the display"),
+        }
+    }
+}
+
+impl std::error::Error for XError {}
+
 #[derive(Copy, Clone, Debug)]
 pub struct Rect {
     pub x: i32,
@@ -0,0 +1,98 @@
+use crate::dl::Library;
+use crate::{Display, Rect};
+use std::ffi::CStr;
+use std::os::raw::{c_int, c_ulong, c_void};
+use std::sync::OnceLock;
+use x11::xlib;
+
+static LIBRARY: OnceLock<Option<Library>> = OnceLock::new();
+
+/// A monitor as reported by RandR.
+#[derive(Clone, Debug)]
+pub struct Monitor {
+    pub name: String,
+    pub rect: Rect,
+    pub primary: bool,
+}
+
+// XRRMonitorInfo
+#[repr(C)]
+struct MonitorInfo {
+    name: xlib::Atom,
+    primary: c_int,
+    automatic: c_int,
+    noutput: c_int,
+    x: c_int,
+    y: c_int,
+    width: c_int,
+    height: c_int,
+    mwidth: c_int,
+    mheight: c_int,
+    outputs: *mut c_ulong,
+}
+
+type GetMonitors =
+    unsafe extern "C" fn(*mut xlib::Display, xlib::Window, c_int, *mut c_int) -> *mut MonitorInfo;
+type FreeMonitors = unsafe extern "C" fn(*mut MonitorInfo);
+
+impl Display {
+    // XRRGetMonitors, XRRFreeMonitors
+    // Returns an empty list if libXrandr is unavailable or the server does
+    // not support RandR 1.5.
+    pub fn get_monitors(&self) -> Vec<Monitor> {
+        let library = match Library::load(&LIBRARY, b"libXrandr.so.2\0") {
+            Some(library) => library,
+            None => return Vec::new(),
+        };
+
+        unsafe {
+            let get = library.symbol::<GetMonitors>(b"XRRGetMonitors\0");
+            let free = library.symbol::<FreeMonitors>(b"XRRFreeMonitors\0");
+            match (get, free) {
+                (Some(get), Some(free)) => read_monitors(self, get, free),
+                _ => Vec::new(),
+            }
+        }
+    }
+}
+
+unsafe fn read_monitors(display: &Display, get: GetMonitors, free: FreeMonitors) -> Vec<Monitor> {
+    let mut length = 0;
+    let ptr = get(
+        display.as_raw(),
+        display.default_window().as_raw(),
+        1, // active monitors only
+        &mut length,
+    );
+    if ptr.is_null() {
+        return Vec::new();
+    }
+
+    let monitors = std::slice::from_raw_parts(ptr, length.max(0) as usize)
+        .iter()
+        .map(|info| {
+            let name = xlib::XGetAtomName(display.as_raw(), info.name);
+            let monitor = Monitor {
+                name: if name.is_null() {
+                    String::new()
+                } else {
+                    CStr::from_ptr(name).to_string_lossy().into_owned()
+                },
+                rect: Rect {
+                    x: info.x,
+                    y: info.y,
+                    width: info.width.max(0) as u32,
+                    height: info.height.max(0) as u32,
+                },
+                primary: info.primary != 0,
+            };
+            if !name.is_null() {
+                xlib::XFree(name as *mut c_void);
+            }
+            monitor
+        })
+        .collect();
+
+    free(ptr);
+    monitors
+}
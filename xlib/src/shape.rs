@@ -0,0 +1,94 @@
+use crate::dl::Library;
+use crate::{Display, Rect, Window};
+use std::os::raw::{c_int, c_uint, c_void};
+use std::sync::OnceLock;
+use x11::xlib;
+
+static LIBRARY: OnceLock<Option<Library>> = OnceLock::new();
+
+// ShapeBounding
+const SHAPE_BOUNDING: c_int = 0;
+
+type QueryExtents = unsafe extern "C" fn(
+    *mut xlib::Display,
+    xlib::Window,
+    *mut c_int,
+    *mut c_int,
+    *mut c_int,
+    *mut c_uint,
+    *mut c_uint,
+    *mut c_int,
+    *mut c_int,
+    *mut c_int,
+    *mut c_uint,
+    *mut c_uint,
+) -> c_int;
+type GetRectangles = unsafe extern "C" fn(
+    *mut xlib::Display,
+    xlib::Window,
+    c_int,
+    *mut c_int,
+    *mut c_int,
+) -> *mut xlib::XRectangle;
+
+impl Display {
+    // XShapeQueryExtents, XShapeGetRectangles
+    // Returns the rectangles that make up the bounding shape of a window,
+    // relative to its origin. Returns `None` if the window is not shaped,
+    // i.e. rectangular, or if libXext is unavailable.
+    pub fn get_shape(&self, window: &Window) -> Option<Vec<Rect>> {
+        let library = Library::load(&LIBRARY, b"libXext.so.6\0")?;
+
+        unsafe {
+            let query = library.symbol::<QueryExtents>(b"XShapeQueryExtents\0")?;
+            let get = library.symbol::<GetRectangles>(b"XShapeGetRectangles\0")?;
+
+            let (mut shaped, mut clip_shaped) = (0, 0);
+            let (mut x, mut y, mut clip_x, mut clip_y) = (0, 0, 0, 0);
+            let (mut width, mut height, mut clip_width, mut clip_height) = (0, 0, 0, 0);
+            let status = query(
+                self.as_raw(),
+                window.as_raw(),
+                &mut shaped,
+                &mut x,
+                &mut y,
+                &mut width,
+                &mut height,
+                &mut clip_shaped,
+                &mut clip_x,
+                &mut clip_y,
+                &mut clip_width,
+                &mut clip_height,
+            );
+            if status == 0 || shaped == 0 {
+                return None;
+            }
+
+            let mut count = 0;
+            let mut ordering = 0;
+            let ptr = get(
+                self.as_raw(),
+                window.as_raw(),
+                SHAPE_BOUNDING,
+                &mut count,
+                &mut ordering,
+            );
+            if ptr.is_null() {
+                return Some(Vec::new());
+            }
+
+            let rects = std::slice::from_raw_parts(ptr, count.max(0) as usize)
+                .iter()
+                .map(|r| Rect {
+                    x: i32::from(r.x),
+                    y: i32::from(r.y),
+                    width: u32::from(r.width),
+                    height: u32::from(r.height),
+                })
+                .collect();
+
+            xlib::XFree(ptr as *mut c_void);
+            Some(rects)
+        }
+    }
+}
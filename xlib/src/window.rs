@@ -16,6 +16,20 @@ impl Default for SetWindowAttributes {
     }
 }
 
+/// The attributes of a window, as reported by XGetWindowAttributes.
+#[derive(Debug, Copy, Clone)]
+pub struct WindowAttributes {
+    /// Position relative to the parent, and size of the window.
+    pub rect: Rect,
+    pub border_width: u32,
+    pub depth: i32,
+    /// Whether the window and all of its ancestors are mapped.
+    pub viewable: bool,
+    pub override_redirect: bool,
+    /// Whether the window is InputOnly, i.e. has no contents.
+    pub input_only: bool,
+}
+
 #[derive(Debug, Copy, Clone)]
 pub struct Window {
     display: XDisplay,
@@ -183,8 +197,30 @@ impl Window {
         }
     }
 
-    // TODO: get window attributes
-    // pub fn get_width(&self){}
+    // XGetWindowAttributes
+    // Returns `None` if the window does not exist.
+    pub fn get_attributes(&self) -> Option<WindowAttributes> {
+        unsafe {
+            let mut attributes: xlib::XWindowAttributes = mem::zeroed();
+            if xlib::XGetWindowAttributes(self.display, self._inner, &mut attributes) == 0 {
+                return None;
+            }
+
+            Some(WindowAttributes {
+                rect: Rect {
+                    x: attributes.x,
+                    y: attributes.y,
+                    width: attributes.width.max(0) as u32,
+                    height: attributes.height.max(0) as u32,
+                },
+                border_width: attributes.border_width.max(0) as u32,
+                depth: attributes.depth,
+                viewable: attributes.map_state == xlib::IsViewable,
+                override_redirect: attributes.override_redirect != 0,
+                input_only: attributes.class == xlib::InputOnly,
+            })
+        }
+    }
 
     pub fn destroy(&mut self) {
         unsafe {